@@ -303,6 +303,8 @@ pub struct LinuxSyscall {
     pub action: LinuxSeccompAction,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub args: Option<Vec<LinuxSeccompArg>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errno_ret: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -1 +1,2 @@
+pub mod x86;
 pub mod x86_64;
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+pub fn is_syscall(syscall_name: &str) -> bool {
+    for (_, name) in SYSCALLS.iter() {
+        if *name == syscall_name {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn to_syscall_number(syscall_name: &str) -> Vec<u32> {
+    SYSCALLS
+        .iter()
+        .filter(|(_, name)| *name == &syscall_name)
+        .map(|(number, _)| *number)
+        .collect()
+}
+
+lazy_static! {
+    pub static ref SYSCALLS: HashMap<u32, &'static str> = {
+        let mut s = HashMap::new();
+        s.insert(0, "restart_syscall");
+        s.insert(1, "exit");
+        s.insert(2, "fork");
+        s.insert(3, "read");
+        s.insert(4, "write");
+        s.insert(5, "open");
+        s.insert(6, "close");
+        s.insert(7, "waitpid");
+        s.insert(8, "creat");
+        s.insert(9, "link");
+        s.insert(10, "unlink");
+        s.insert(11, "execve");
+        s.insert(12, "chdir");
+        s.insert(13, "time");
+        s.insert(14, "mknod");
+        s.insert(15, "chmod");
+        s.insert(16, "lchown");
+        s.insert(17, "break");
+        s.insert(18, "oldstat");
+        s.insert(19, "lseek");
+        s.insert(20, "getpid");
+        s.insert(21, "mount");
+        s.insert(22, "umount");
+        s.insert(23, "setuid");
+        s.insert(24, "getuid");
+        s.insert(25, "stime");
+        s.insert(26, "ptrace");
+        s.insert(27, "alarm");
+        s.insert(28, "oldfstat");
+        s.insert(29, "pause");
+        s.insert(30, "utime");
+        s.insert(31, "stty");
+        s.insert(32, "gtty");
+        s.insert(33, "access");
+        s.insert(34, "nice");
+        s.insert(35, "ftime");
+        s.insert(36, "sync");
+        s.insert(37, "kill");
+        s.insert(38, "rename");
+        s.insert(39, "mkdir");
+        s.insert(40, "rmdir");
+        s.insert(41, "dup");
+        s.insert(42, "pipe");
+        s.insert(43, "times");
+        s.insert(44, "prof");
+        s.insert(45, "brk");
+        s.insert(46, "setgid");
+        s.insert(47, "getgid");
+        s.insert(48, "signal");
+        s.insert(49, "geteuid");
+        s.insert(50, "getegid");
+        s.insert(51, "acct");
+        s.insert(52, "umount2");
+        s.insert(53, "lock");
+        s.insert(54, "ioctl");
+        s.insert(55, "fcntl");
+        s.insert(56, "mpx");
+        s.insert(57, "setpgid");
+        s.insert(58, "ulimit");
+        s.insert(59, "oldolduname");
+        s.insert(60, "umask");
+        s.insert(61, "chroot");
+        s.insert(62, "ustat");
+        s.insert(63, "dup2");
+        s.insert(64, "getppid");
+        s.insert(65, "getpgrp");
+        s.insert(66, "setsid");
+        s.insert(67, "sigaction");
+        s.insert(68, "sgetmask");
+        s.insert(69, "ssetmask");
+        s.insert(70, "setreuid");
+        s.insert(71, "setregid");
+        s.insert(72, "sigsuspend");
+        s.insert(73, "sigpending");
+        s.insert(74, "sethostname");
+        s.insert(75, "setrlimit");
+        s.insert(76, "getrlimit");
+        s.insert(77, "getrusage");
+        s.insert(78, "gettimeofday");
+        s.insert(79, "settimeofday");
+        s.insert(80, "getgroups");
+        s.insert(81, "setgroups");
+        s.insert(82, "select");
+        s.insert(83, "symlink");
+        s.insert(84, "oldlstat");
+        s.insert(85, "readlink");
+        s.insert(86, "uselib");
+        s.insert(87, "swapon");
+        s.insert(88, "reboot");
+        s.insert(89, "readdir");
+        s.insert(90, "mmap");
+        s.insert(91, "munmap");
+        s.insert(92, "truncate");
+        s.insert(93, "ftruncate");
+        s.insert(94, "fchmod");
+        s.insert(95, "fchown");
+        s.insert(96, "getpriority");
+        s.insert(97, "setpriority");
+        s.insert(98, "profil");
+        s.insert(99, "statfs");
+        s.insert(100, "fstatfs");
+        s.insert(101, "ioperm");
+        s.insert(102, "socketcall");
+        s.insert(103, "syslog");
+        s.insert(104, "setitimer");
+        s.insert(105, "getitimer");
+        s.insert(106, "stat");
+        s.insert(107, "lstat");
+        s.insert(108, "fstat");
+        s.insert(109, "olduname");
+        s.insert(110, "iopl");
+        s.insert(111, "vhangup");
+        s.insert(112, "idle");
+        s.insert(113, "vm86old");
+        s.insert(114, "wait4");
+        s.insert(115, "swapoff");
+        s.insert(116, "sysinfo");
+        s.insert(117, "ipc");
+        s.insert(118, "fsync");
+        s.insert(119, "sigreturn");
+        s.insert(120, "clone");
+        s.insert(121, "setdomainname");
+        s.insert(122, "uname");
+        s.insert(123, "modify_ldt");
+        s.insert(124, "adjtimex");
+        s.insert(125, "mprotect");
+        s.insert(126, "sigprocmask");
+        s.insert(127, "create_module");
+        s.insert(128, "init_module");
+        s.insert(129, "delete_module");
+        s.insert(130, "get_kernel_syms");
+        s.insert(131, "quotactl");
+        s.insert(132, "getpgid");
+        s.insert(133, "fchdir");
+        s.insert(134, "bdflush");
+        s.insert(135, "sysfs");
+        s.insert(136, "personality");
+        s.insert(137, "afs_syscall");
+        s.insert(138, "setfsuid");
+        s.insert(139, "setfsgid");
+        s.insert(140, "_llseek");
+        s.insert(141, "getdents");
+        s.insert(142, "_newselect");
+        s.insert(143, "flock");
+        s.insert(144, "msync");
+        s.insert(145, "readv");
+        s.insert(146, "writev");
+        s.insert(147, "getsid");
+        s.insert(148, "fdatasync");
+        s.insert(149, "_sysctl");
+        s.insert(150, "mlock");
+        s.insert(151, "munlock");
+        s.insert(152, "mlockall");
+        s.insert(153, "munlockall");
+        s.insert(154, "sched_setparam");
+        s.insert(155, "sched_getparam");
+        s.insert(156, "sched_setscheduler");
+        s.insert(157, "sched_getscheduler");
+        s.insert(158, "sched_yield");
+        s.insert(159, "sched_get_priority_max");
+        s.insert(160, "sched_get_priority_min");
+        s.insert(161, "sched_rr_get_interval");
+        s.insert(162, "nanosleep");
+        s.insert(163, "mremap");
+        s.insert(164, "setresuid");
+        s.insert(165, "getresuid");
+        s.insert(166, "vm86");
+        s.insert(167, "query_module");
+        s.insert(168, "poll");
+        s.insert(169, "nfsservctl");
+        s.insert(170, "setresgid");
+        s.insert(171, "getresgid");
+        s.insert(172, "prctl");
+        s.insert(173, "rt_sigreturn");
+        s.insert(174, "rt_sigaction");
+        s.insert(175, "rt_sigprocmask");
+        s.insert(176, "rt_sigpending");
+        s.insert(177, "rt_sigtimedwait");
+        s.insert(178, "rt_sigqueueinfo");
+        s.insert(179, "rt_sigsuspend");
+        s.insert(180, "pread64");
+        s.insert(181, "pwrite64");
+        s.insert(182, "chown");
+        s.insert(183, "getcwd");
+        s.insert(184, "capget");
+        s.insert(185, "capset");
+        s.insert(186, "sigaltstack");
+        s.insert(187, "sendfile");
+        s.insert(188, "getpmsg");
+        s.insert(189, "putpmsg");
+        s.insert(190, "vfork");
+        s.insert(191, "ugetrlimit");
+        s.insert(192, "mmap2");
+        s.insert(193, "truncate64");
+        s.insert(194, "ftruncate64");
+        s.insert(195, "stat64");
+        s.insert(196, "lstat64");
+        s.insert(197, "fstat64");
+        s.insert(198, "lchown32");
+        s.insert(199, "getuid32");
+        s.insert(200, "getgid32");
+        s.insert(201, "geteuid32");
+        s.insert(202, "getegid32");
+        s.insert(203, "setreuid32");
+        s.insert(204, "setregid32");
+        s.insert(205, "getgroups32");
+        s.insert(206, "setgroups32");
+        s.insert(207, "fchown32");
+        s.insert(208, "setresuid32");
+        s.insert(209, "getresuid32");
+        s.insert(210, "setresgid32");
+        s.insert(211, "getresgid32");
+        s.insert(212, "chown32");
+        s.insert(213, "setuid32");
+        s.insert(214, "setgid32");
+        s.insert(215, "setfsuid32");
+        s.insert(216, "setfsgid32");
+        s.insert(217, "pivot_root");
+        s.insert(218, "mincore");
+        s.insert(219, "madvise");
+        s.insert(220, "getdents64");
+        s.insert(221, "fcntl64");
+        s.insert(224, "gettid");
+        s.insert(225, "readahead");
+        s.insert(226, "setxattr");
+        s.insert(227, "lsetxattr");
+        s.insert(228, "fsetxattr");
+        s.insert(229, "getxattr");
+        s.insert(230, "lgetxattr");
+        s.insert(231, "fgetxattr");
+        s.insert(232, "listxattr");
+        s.insert(233, "llistxattr");
+        s.insert(234, "flistxattr");
+        s.insert(235, "removexattr");
+        s.insert(236, "lremovexattr");
+        s.insert(237, "fremovexattr");
+        s.insert(238, "tkill");
+        s.insert(239, "sendfile64");
+        s.insert(240, "futex");
+        s.insert(241, "sched_setaffinity");
+        s.insert(242, "sched_getaffinity");
+        s.insert(243, "set_thread_area");
+        s.insert(244, "get_thread_area");
+        s.insert(245, "io_setup");
+        s.insert(246, "io_destroy");
+        s.insert(247, "io_getevents");
+        s.insert(248, "io_submit");
+        s.insert(249, "io_cancel");
+        s.insert(250, "fadvise64");
+        s.insert(252, "exit_group");
+        s.insert(253, "lookup_dcookie");
+        s.insert(254, "epoll_create");
+        s.insert(255, "epoll_ctl");
+        s.insert(256, "epoll_wait");
+        s.insert(257, "remap_file_pages");
+        s.insert(258, "set_tid_address");
+        s.insert(259, "timer_create");
+        s.insert(260, "timer_settime");
+        s.insert(261, "timer_gettime");
+        s.insert(262, "timer_getoverrun");
+        s.insert(263, "timer_delete");
+        s.insert(264, "clock_settime");
+        s.insert(265, "clock_gettime");
+        s.insert(266, "clock_getres");
+        s.insert(267, "clock_nanosleep");
+        s.insert(268, "statfs64");
+        s.insert(269, "fstatfs64");
+        s.insert(270, "tgkill");
+        s.insert(271, "utimes");
+        s.insert(272, "fadvise64_64");
+        s.insert(273, "vserver");
+        s.insert(274, "mbind");
+        s.insert(275, "get_mempolicy");
+        s.insert(276, "set_mempolicy");
+        s.insert(277, "mq_open");
+        s.insert(278, "mq_unlink");
+        s.insert(279, "mq_timedsend");
+        s.insert(280, "mq_timedreceive");
+        s.insert(281, "mq_notify");
+        s.insert(282, "mq_getsetattr");
+        s.insert(283, "kexec_load");
+        s.insert(284, "waitid");
+        s.insert(286, "add_key");
+        s.insert(287, "request_key");
+        s.insert(288, "keyctl");
+        s.insert(289, "ioprio_set");
+        s.insert(290, "ioprio_get");
+        s.insert(291, "inotify_init");
+        s.insert(292, "inotify_add_watch");
+        s.insert(293, "inotify_rm_watch");
+        s.insert(294, "migrate_pages");
+        s.insert(295, "openat");
+        s.insert(296, "mkdirat");
+        s.insert(297, "mknodat");
+        s.insert(298, "fchownat");
+        s.insert(299, "futimesat");
+        s.insert(300, "fstatat64");
+        s.insert(301, "unlinkat");
+        s.insert(302, "renameat");
+        s.insert(303, "linkat");
+        s.insert(304, "symlinkat");
+        s.insert(305, "readlinkat");
+        s.insert(306, "fchmodat");
+        s.insert(307, "faccessat");
+        s.insert(308, "pselect6");
+        s.insert(309, "ppoll");
+        s.insert(310, "unshare");
+        s.insert(311, "set_robust_list");
+        s.insert(312, "get_robust_list");
+        s.insert(313, "splice");
+        s.insert(314, "sync_file_range");
+        s.insert(315, "tee");
+        s.insert(316, "vmsplice");
+        s.insert(317, "move_pages");
+        s.insert(318, "getcpu");
+        s.insert(319, "epoll_pwait");
+        s.insert(320, "utimensat");
+        s.insert(321, "signalfd");
+        s.insert(322, "timerfd_create");
+        s.insert(323, "eventfd");
+        s.insert(324, "fallocate");
+        s.insert(325, "timerfd_settime");
+        s.insert(326, "timerfd_gettime");
+        s.insert(327, "signalfd4");
+        s.insert(328, "eventfd2");
+        s.insert(329, "epoll_create1");
+        s.insert(330, "dup3");
+        s.insert(331, "pipe2");
+        s.insert(332, "inotify_init1");
+        s.insert(333, "preadv");
+        s.insert(334, "pwritev");
+        s.insert(335, "rt_tgsigqueueinfo");
+        s.insert(336, "perf_event_open");
+        s.insert(337, "recvmmsg");
+        s.insert(338, "fanotify_init");
+        s.insert(339, "fanotify_mark");
+        s.insert(340, "prlimit64");
+        s.insert(341, "name_to_handle_at");
+        s.insert(342, "open_by_handle_at");
+        s.insert(343, "clock_adjtime");
+        s.insert(344, "syncfs");
+        s.insert(345, "sendmmsg");
+        s.insert(346, "setns");
+        s.insert(347, "process_vm_readv");
+        s.insert(348, "process_vm_writev");
+        s.insert(349, "kcmp");
+        s.insert(350, "finit_module");
+        s.insert(351, "sched_setattr");
+        s.insert(352, "sched_getattr");
+        s.insert(353, "renameat2");
+        s.insert(354, "seccomp");
+        s.insert(355, "getrandom");
+        s.insert(356, "memfd_create");
+        s.insert(357, "bpf");
+        s.insert(358, "execveat");
+        s.insert(359, "socket");
+        s.insert(360, "socketpair");
+        s.insert(361, "bind");
+        s.insert(362, "connect");
+        s.insert(363, "listen");
+        s.insert(364, "accept4");
+        s.insert(365, "getsockopt");
+        s.insert(366, "setsockopt");
+        s.insert(367, "getsockname");
+        s.insert(368, "getpeername");
+        s.insert(369, "sendto");
+        s.insert(370, "sendmsg");
+        s.insert(371, "recvfrom");
+        s.insert(372, "recvmsg");
+        s.insert(373, "shutdown");
+        s.insert(374, "userfaultfd");
+        s.insert(375, "membarrier");
+        s.insert(376, "mlock2");
+        s.insert(377, "copy_file_range");
+        s.insert(378, "preadv2");
+        s.insert(379, "pwritev2");
+        s.insert(380, "pkey_mprotect");
+        s.insert(381, "pkey_alloc");
+        s.insert(382, "pkey_free");
+        s.insert(383, "statx");
+        s.insert(384, "arch_prctl");
+        s.insert(385, "io_pgetevents");
+        s.insert(386, "rseq");
+        s.insert(393, "semget");
+        s.insert(394, "semctl");
+        s.insert(395, "shmget");
+        s.insert(396, "shmctl");
+        s.insert(397, "shmat");
+        s.insert(398, "shmdt");
+        s.insert(399, "msgget");
+        s.insert(400, "msgsnd");
+        s.insert(401, "msgrcv");
+        s.insert(402, "msgctl");
+        s.insert(403, "clock_gettime64");
+        s.insert(404, "clock_settime64");
+        s.insert(405, "clock_adjtime64");
+        s.insert(406, "clock_getres_time64");
+        s.insert(407, "clock_nanosleep_time64");
+        s.insert(408, "timer_gettime64");
+        s.insert(409, "timer_settime64");
+        s.insert(410, "timerfd_gettime64");
+        s.insert(411, "timerfd_settime64");
+        s.insert(412, "utimensat_time64");
+        s.insert(413, "pselect6_time64");
+        s.insert(414, "ppoll_time64");
+        s.insert(416, "io_pgetevents_time64");
+        s.insert(417, "recvmmsg_time64");
+        s.insert(418, "mq_timedsend_time64");
+        s.insert(419, "mq_timedreceive_time64");
+        s.insert(420, "semtimedop_time64");
+        s.insert(421, "rt_sigtimedwait_time64");
+        s.insert(422, "futex_time64");
+        s.insert(423, "sched_rr_get_interval_time64");
+        s.insert(424, "pidfd_send_signal");
+        s.insert(425, "io_uring_setup");
+        s.insert(426, "io_uring_enter");
+        s.insert(427, "io_uring_register");
+        s.insert(428, "open_tree");
+        s.insert(429, "move_mount");
+        s.insert(430, "fsopen");
+        s.insert(431, "fsconfig");
+        s.insert(432, "fsmount");
+        s.insert(433, "fspick");
+        s.insert(434, "pidfd_open");
+        s.insert(435, "clone3");
+        s.insert(436, "close_range");
+        s.insert(437, "openat2");
+        s.insert(438, "pidfd_getfd");
+        s.insert(439, "faccessat2");
+        s
+    };
+}
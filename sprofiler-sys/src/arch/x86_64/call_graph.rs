@@ -0,0 +1,181 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use iced_x86::{FlowControl, Instruction};
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+
+use crate::arch::x86_64::syscall_site::{self, SyscallNumber};
+
+#[derive(Debug, Default)]
+struct Function {
+    /// Start addresses of the functions called or jumped to
+    callees: BTreeSet<u64>,
+    syscalls: BTreeSet<u64>,
+    /// `syscall` instructions whose number is not known
+    unresolved: usize,
+}
+
+/// Syscalls a function can reach through direct calls and jumps
+#[derive(Debug, Default, PartialEq)]
+pub struct Reachable {
+    pub syscalls: BTreeSet<u64>,
+    pub unresolved: usize,
+}
+
+/// Direct calls and jumps between the functions of an ELF file
+///
+/// Functions start at symbols and at the targets of direct calls, so the internal
+/// functions of a stripped library are found too. Indirect calls are not followed.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    functions: BTreeMap<u64, Function>,
+}
+
+impl CallGraph {
+    pub fn build(obj_file: &object::File) -> Result<Self> {
+        let symbols: Vec<u64> = obj_file
+            .symbols()
+            .chain(obj_file.dynamic_symbols())
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+            .map(|symbol| symbol.address())
+            .collect();
+
+        let mut sections = vec![];
+        for section in obj_file
+            .sections()
+            .filter(|section| section.kind() == SectionKind::Text)
+        {
+            sections.push(syscall_site::decode(section.data()?, section.address()));
+        }
+
+        Ok(CallGraph::from_code(&sections, &symbols))
+    }
+
+    /// `sections` are decoded executable sections, `symbols` are function addresses
+    pub fn from_code(sections: &[Vec<Instruction>], symbols: &[u64]) -> Self {
+        let ranges: Vec<(u64, u64)> = sections
+            .iter()
+            .filter_map(|code| Some((code.first()?.ip(), code.last()?.next_ip())))
+            .collect();
+        let in_code = |address: u64| {
+            ranges
+                .iter()
+                .any(|(start, end)| *start <= address && address < *end)
+        };
+
+        let mut starts: BTreeSet<u64> = symbols.iter().copied().filter(|a| in_code(*a)).collect();
+        starts.extend(ranges.iter().map(|(start, _)| *start));
+        starts.extend(
+            sections
+                .iter()
+                .flatten()
+                .filter(|instr| instr.flow_control() == FlowControl::Call)
+                .map(|instr| instr.near_branch_target())
+                .filter(|target| in_code(*target)),
+        );
+        let function_of = |address: u64| starts.range(..=address).next_back().copied();
+
+        let mut functions: BTreeMap<u64, Function> = starts
+            .iter()
+            .map(|start| (*start, Function::default()))
+            .collect();
+
+        for code in sections {
+            for instr in code.iter().filter(|instr| {
+                matches!(
+                    instr.flow_control(),
+                    FlowControl::Call
+                        | FlowControl::UnconditionalBranch
+                        | FlowControl::ConditionalBranch
+                )
+            }) {
+                let target = instr.near_branch_target();
+                if !in_code(target) {
+                    continue;
+                }
+                if let (Some(caller), Some(callee)) = (function_of(instr.ip()), function_of(target))
+                {
+                    // jumps inside of a function are not calls
+                    if caller != callee {
+                        functions.entry(caller).or_default().callees.insert(callee);
+                    }
+                }
+            }
+
+            for site in syscall_site::find_in_instructions(code) {
+                let function = match function_of(site.address) {
+                    Some(start) => functions.entry(start).or_default(),
+                    None => continue,
+                };
+                match site.number {
+                    SyscallNumber::Constant(number) => {
+                        function.syscalls.insert(number);
+                    }
+                    SyscallNumber::Unresolved(_) => function.unresolved += 1,
+                }
+            }
+        }
+
+        CallGraph { functions }
+    }
+
+    /// Syscalls of the function at `address` and of the functions it reaches
+    pub fn reachable(&self, address: u64) -> Reachable {
+        let mut reachable = Reachable::default();
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![address];
+
+        while let Some(address) = stack.pop() {
+            if !visited.insert(address) {
+                continue;
+            }
+            if let Some(function) = self.functions.get(&address) {
+                reachable.syscalls.extend(&function.syscalls);
+                reachable.unresolved += function.unresolved;
+                stack.extend(function.callees.iter().copied());
+            }
+        }
+
+        reachable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reachable_syscalls() {
+        #[rustfmt::skip]
+        let code = [
+            0xb8, 0x27, 0x00, 0x00, 0x00, // 0x1000: mov eax, 0x27
+            0x0f, 0x05,                   //         syscall
+            0xc3,                         //         ret
+            0xe8, 0xf3, 0xff, 0xff, 0xff, // 0x1008: call 0x1000
+            0x31, 0xc0,                   //         xor eax, eax
+            0x0f, 0x05,                   //         syscall
+            0xc3,                         //         ret
+            0xe9, 0xf1, 0xff, 0xff, 0xff, // 0x1012: jmp 0x1008
+            0x48, 0x8b, 0x07,             // 0x1017: mov rax, [rdi]
+            0x0f, 0x05,                   //         syscall
+            0xc3,                         //         ret
+        ];
+        let sections = vec![syscall_site::decode(&code, 0x1000)];
+        let graph = CallGraph::from_code(&sections, &[0x1008, 0x1012, 0x1017]);
+
+        assert_eq!(
+            graph.reachable(0x1012),
+            Reachable {
+                syscalls: vec![0, 39].into_iter().collect(),
+                unresolved: 0,
+            }
+        );
+        assert_eq!(
+            graph.reachable(0x1017),
+            Reachable {
+                syscalls: BTreeSet::new(),
+                unresolved: 1,
+            }
+        );
+    }
+}
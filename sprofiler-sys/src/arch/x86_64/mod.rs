@@ -2,6 +2,9 @@ use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
+pub mod call_graph;
+pub mod syscall_site;
+
 pub fn is_syscall(syscall_name: &str) -> bool {
     for (_, name) in SYSCALLS.iter() {
         if *name == syscall_name {
@@ -168,9 +171,9 @@ lazy_static! {
         s.insert(143, "sched_getparam");
         s.insert(144, "sched_setscheduler");
         s.insert(145, "sched_getscheduler");
-        s.insert(146, "sched_get_priority_m");
-        s.insert(147, "sched_get_priority_m");
-        s.insert(148, "sched_rr_get_interva");
+        s.insert(146, "sched_get_priority_max");
+        s.insert(147, "sched_get_priority_min");
+        s.insert(148, "sched_rr_get_interval");
         s.insert(149, "mlock");
         s.insert(150, "munlock");
         s.insert(151, "mlockall");
@@ -369,6 +372,7 @@ lazy_static! {
         s.insert(433, "fspick");
         s.insert(434, "pidfd_open");
         s.insert(435, "clone3");
+        s.insert(436, "close_range");
         s.insert(437, "openat2");
         s.insert(438, "pidfd_getfd");
         s.insert(439, "faccessat2");
@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use iced_x86::{
+    Decoder, DecoderOptions, FlowControl, Instruction, InstructionInfoFactory, Mnemonic, OpAccess,
+    OpKind, Register,
+};
+use object::{Object, ObjectSection, SectionKind};
+
+/// Instructions looked at before a `syscall` to find the value of rax
+const MAX_BACKTRACK: usize = 32;
+
+/// Value of rax when a `syscall` instruction is executed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyscallNumber {
+    Constant(u64),
+    /// Why the number is not known statically, e.g. it is loaded from memory
+    Unresolved(&'static str),
+}
+
+/// A `syscall` instruction in an executable section
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyscallSite {
+    pub address: u64,
+    pub number: SyscallNumber,
+}
+
+/// Find the `syscall` instructions of an ELF file, e.g. a static or musl-static binary
+pub fn find_syscall_sites(obj_file: &object::File) -> Result<Vec<SyscallSite>> {
+    let mut sites = vec![];
+    for section in obj_file
+        .sections()
+        .filter(|section| section.kind() == SectionKind::Text)
+    {
+        sites.extend(find_in_code(section.data()?, section.address()));
+    }
+    Ok(sites)
+}
+
+/// Find the `syscall` instructions in `code` which is loaded at `address`
+pub fn find_in_code(code: &[u8], address: u64) -> Vec<SyscallSite> {
+    find_in_instructions(&decode(code, address))
+}
+
+/// Linear sweep of `code` which is loaded at `address`
+pub fn decode(code: &[u8], address: u64) -> Vec<Instruction> {
+    let mut decoder = Decoder::with_ip(64, code, address, DecoderOptions::NONE);
+    let mut instructions = vec![];
+    let mut instr = Instruction::default();
+    while decoder.can_decode() {
+        decoder.decode_out(&mut instr);
+        instructions.push(instr);
+    }
+    instructions
+}
+
+/// Find the `syscall` instructions in decoded code
+pub fn find_in_instructions(instructions: &[Instruction]) -> Vec<SyscallSite> {
+    // rax may have another value when a block is entered by a jump
+    let targets: HashSet<u64> = instructions
+        .iter()
+        .filter(|instr| {
+            matches!(
+                instr.flow_control(),
+                FlowControl::UnconditionalBranch
+                    | FlowControl::ConditionalBranch
+                    | FlowControl::Call
+            )
+        })
+        .map(|instr| instr.near_branch_target())
+        .collect();
+
+    let mut factory = InstructionInfoFactory::new();
+    instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, instr)| instr.mnemonic() == Mnemonic::Syscall)
+        .map(|(i, instr)| SyscallSite {
+            address: instr.ip(),
+            number: if targets.contains(&instr.ip()) {
+                SyscallNumber::Unresolved("rax is set in another block")
+            } else {
+                resolve_rax(&instructions[..i], &targets, &mut factory)
+            },
+        })
+        .collect()
+}
+
+/// Value written by `instr` to `register`
+enum Definition {
+    Constant(u64),
+    /// Copied from another register
+    Copy(Register),
+    Unknown,
+}
+
+fn definition(instr: &Instruction) -> Definition {
+    if instr.op_count() != 2 || instr.op0_kind() != OpKind::Register {
+        return Definition::Unknown;
+    }
+    let dst = instr.op0_register();
+    // 8 and 16-bit writes keep the upper bits
+    if !dst.is_gpr32() && !dst.is_gpr64() {
+        return Definition::Unknown;
+    }
+
+    match (instr.mnemonic(), instr.op1_kind()) {
+        (Mnemonic::Mov, OpKind::Immediate32 | OpKind::Immediate32to64 | OpKind::Immediate64) => {
+            let value = instr.immediate(1);
+            Definition::Constant(if dst.is_gpr32() {
+                value & u64::from(u32::MAX)
+            } else {
+                value
+            })
+        }
+        (Mnemonic::Xor | Mnemonic::Sub, OpKind::Register) if instr.op1_register() == dst => {
+            Definition::Constant(0)
+        }
+        (Mnemonic::Mov, OpKind::Register)
+            if instr.op1_register().is_gpr32() || instr.op1_register().is_gpr64() =>
+        {
+            Definition::Copy(instr.op1_register().full_register())
+        }
+        _ => Definition::Unknown,
+    }
+}
+
+/// Walk back from a `syscall` in its basic block to the instruction which sets rax
+fn resolve_rax(
+    before: &[Instruction],
+    targets: &HashSet<u64>,
+    factory: &mut InstructionInfoFactory,
+) -> SyscallNumber {
+    let mut register = Register::RAX;
+
+    for instr in before.iter().rev().take(MAX_BACKTRACK) {
+        if instr.mnemonic() == Mnemonic::Syscall {
+            return SyscallNumber::Unresolved("rax is the result of a syscall");
+        }
+        match instr.flow_control() {
+            FlowControl::Next | FlowControl::ConditionalBranch => {}
+            FlowControl::Call | FlowControl::IndirectCall => {
+                return SyscallNumber::Unresolved("rax is the result of a call")
+            }
+            _ => return SyscallNumber::Unresolved("rax is set in another block"),
+        }
+
+        let writes = factory.info(instr).used_registers().iter().any(|used| {
+            used.register().full_register() == register
+                && matches!(
+                    used.access(),
+                    OpAccess::Write
+                        | OpAccess::CondWrite
+                        | OpAccess::ReadWrite
+                        | OpAccess::ReadCondWrite
+                )
+        });
+        if writes {
+            match definition(instr) {
+                Definition::Constant(value) => return SyscallNumber::Constant(value),
+                Definition::Copy(source) => register = source,
+                Definition::Unknown => return SyscallNumber::Unresolved("rax is computed"),
+            }
+        }
+
+        if targets.contains(&instr.ip()) {
+            return SyscallNumber::Unresolved("rax is set in another block");
+        }
+    }
+
+    SyscallNumber::Unresolved("rax is set in another block")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_syscall_numbers() {
+        #[rustfmt::skip]
+        let code = [
+            0xb8, 0x27, 0x00, 0x00, 0x00,             // mov eax, 0x27
+            0x0f, 0x05,                               // syscall
+            0x31, 0xc0,                               // xor eax, eax
+            0x90,                                     // nop
+            0x0f, 0x05,                               // syscall
+            0x48, 0xc7, 0xc0, 0x3c, 0x00, 0x00, 0x00, // mov rax, 0x3c
+            0x0f, 0x05,                               // syscall
+            0xbf, 0xe7, 0x00, 0x00, 0x00,             // mov edi, 0xe7
+            0x89, 0xf8,                               // mov eax, edi
+            0x0f, 0x05,                               // syscall
+        ];
+
+        let numbers: Vec<SyscallNumber> = find_in_code(&code, 0x401000)
+            .into_iter()
+            .map(|site| site.number)
+            .collect();
+        assert_eq!(
+            numbers,
+            vec![
+                SyscallNumber::Constant(39),
+                SyscallNumber::Constant(0),
+                SyscallNumber::Constant(60),
+                SyscallNumber::Constant(231),
+            ]
+        );
+    }
+
+    #[test]
+    fn report_unresolved_syscall_numbers() {
+        #[rustfmt::skip]
+        let code = [
+            0x48, 0x89, 0xf8,             // syscall(2): mov rax, rdi
+            0x0f, 0x05,                   // syscall
+            0xc3,                         // ret
+            0x48, 0x8b, 0x07,             // mov rax, [rdi]
+            0x0f, 0x05,                   // syscall
+            0xe8, 0xf0, 0xff, 0xff, 0xff, // call 0x401000
+            0x0f, 0x05,                   // syscall
+        ];
+
+        let sites = find_in_code(&code, 0x401000);
+        assert_eq!(
+            sites.iter().map(|site| site.address).collect::<Vec<_>>(),
+            vec![0x401003, 0x401009, 0x401010]
+        );
+        assert_eq!(
+            sites
+                .iter()
+                .map(|site| site.number)
+                .collect::<Vec<SyscallNumber>>(),
+            vec![
+                SyscallNumber::Unresolved("rax is set in another block"),
+                SyscallNumber::Unresolved("rax is computed"),
+                SyscallNumber::Unresolved("rax is the result of a call"),
+            ]
+        );
+    }
+}
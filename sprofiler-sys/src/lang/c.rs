@@ -1,84 +1,265 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
-use object::{Object, ObjectSymbol};
+use object::{Architecture, Object};
 use oci_runtime_spec::{Arch, LinuxSeccomp, LinuxSeccompAction, LinuxSyscall};
+use serde_derive::Serialize;
 
+use crate::arch::x86_64::syscall_site::{self, SyscallNumber, SyscallSite};
+use crate::arch::x86_64::SYSCALLS;
+use crate::lang::elf::{self, Import};
+use crate::lang::libc::{self, LinkInfo};
+use crate::lang::rootfs;
 use crate::lang::SeccompProfiler;
 
 #[derive(Default)]
 pub struct CSeccompProfiler {
     pub destination: PathBuf,
     pub target_bin: PathBuf,
-    pub syscall_map: PathBuf,
+    /// Extends or overrides the bundled map of the detected libc
+    pub syscall_map: Option<PathBuf>,
+    /// Root filesystem of the image, `target_bin` is a path in it and its
+    /// shared libraries are analyzed too
+    pub rootfs: Option<PathBuf>,
+}
+
+/// Syscalls of the executable or of one of its shared libraries
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectSyscalls {
+    /// Path of the executable or DT_NEEDED name of the library
+    pub name: String,
+    /// Path in the root filesystem, none if the library is not found
+    pub path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub needed_by: Option<String>,
+    pub syscalls: Vec<String>,
+    /// `syscall` instructions whose number is not known
+    pub unresolved_sites: usize,
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.libraries.json
+pub fn libraries_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("libraries.json")
+}
+
+fn seccomp(syscall: LinuxSyscall) -> LinuxSeccomp {
+    LinuxSeccomp {
+        syscalls: Some(vec![syscall]),
+        default_action: LinuxSeccompAction::SCMP_ACT_ERRNO,
+        architectures: Some(vec![Arch::SCMP_ARCH_X86_64]),
+    }
 }
 
 impl SeccompProfiler for CSeccompProfiler {
     fn analyze(&self) -> Result<LinuxSeccomp> {
-        let syscall: LinuxSyscall = self.run()?;
+        let (syscall, _) = self.run()?;
 
-        Ok(LinuxSeccomp {
-            syscalls: Some(vec![syscall]),
-            default_action: LinuxSeccompAction::SCMP_ACT_ERRNO,
-            architectures: Some(vec![Arch::SCMP_ARCH_X86_64]),
-        })
+        Ok(seccomp(syscall))
     }
 
     fn output(&self) -> Result<()> {
+        let (syscall, objects) = self.run()?;
+
         let file = File::create(&self.destination)?;
-        let seccomp = self.analyze()?;
-        serde_json::to_writer(file, &seccomp)?;
+        serde_json::to_writer(file, &seccomp(syscall))?;
+
+        if self.rootfs.is_some() {
+            for object in &objects {
+                println!(
+                    "{}: {} syscalls, {} unresolved syscall sites",
+                    object.name,
+                    object.syscalls.len(),
+                    object.unresolved_sites
+                );
+            }
+            let file = File::create(libraries_path(&self.destination))?;
+            serde_json::to_writer(file, &objects)?;
+        }
         Ok(())
     }
 }
 
-fn allow_syscall_list_from_symbols(
-    symbols: Vec<String>,
-    fn_to_syscall_table: HashMap<String, Vec<String>>,
+/// A versioned entry of the map, e.g. realpath@GLIBC_2.3, takes precedence over the plain name
+fn allow_syscall_list_from_imports(
+    imports: &[Import],
+    fn_to_syscall_table: &HashMap<String, Vec<String>>,
 ) -> Vec<String> {
     let mut syscalls = Vec::<String>::new();
-    for symbol in symbols {
-        if let Some(allow_syscalls) = fn_to_syscall_table.get(&symbol) {
+    for import in imports {
+        if let Some(allow_syscalls) = fn_to_syscall_table
+            .get(&import.to_string())
+            .or_else(|| fn_to_syscall_table.get(&import.name))
+        {
             syscalls.extend::<Vec<String>>(allow_syscalls.to_vec());
         }
     }
     syscalls
 }
 
+/// Syscalls invoked directly by `syscall` instructions, the unresolved sites are reported
+fn allow_syscall_list_from_sites(sites: &[SyscallSite]) -> Vec<String> {
+    let mut syscalls = Vec::<String>::new();
+    for site in sites {
+        let name = match site.number {
+            SyscallNumber::Constant(number) => u32::try_from(number)
+                .ok()
+                .and_then(|number| SYSCALLS.get(&number)),
+            SyscallNumber::Unresolved(reason) => {
+                eprintln!("unresolved syscall at {:#x}: {}", site.address, reason);
+                continue;
+            }
+        };
+        match name {
+            Some(name) => syscalls.push(name.to_string()),
+            None => eprintln!("unknown syscall number at {:#x}", site.address),
+        }
+    }
+    syscalls
+}
+
 impl CSeccompProfiler {
-    fn run(&self) -> Result<LinuxSyscall> {
-        let bin_data = std::fs::read(&self.target_bin)?;
-        let obj_file = object::File::parse(&*bin_data)?;
+    /// Syscalls of the imports and the `syscall` instructions of an ELF object
+    fn analyze_object(
+        &self,
+        data: &[u8],
+        fn_to_syscall_table: &HashMap<String, Vec<String>>,
+        syscall_sites: bool,
+    ) -> Result<(Vec<String>, usize)> {
+        let imports = elf::imports(data)?;
 
-        let fnames: Vec<&str> = obj_file
-            .dynamic_symbols()
-            .filter_map(|symbol| symbol.name().ok())
-            .collect();
+        imports.iter().for_each(|import| println!("{}", import));
 
-        fnames.iter().for_each(|name| println!("{}", name));
+        let mut syscalls = allow_syscall_list_from_imports(&imports, fn_to_syscall_table);
+        let mut unresolved = 0;
+        // static binaries have no imports, libc is linked in and its syscalls are found here
+        if syscall_sites {
+            let obj_file = object::File::parse(data)?;
+            // the decoder and the syscall numbers are those of x86_64
+            if obj_file.architecture() == Architecture::X86_64 {
+                let sites = syscall_site::find_syscall_sites(&obj_file)?;
+                unresolved = sites
+                    .iter()
+                    .filter(|site| matches!(site.number, SyscallNumber::Unresolved(_)))
+                    .count();
+                syscalls.extend(allow_syscall_list_from_sites(&sites));
+            } else {
+                eprintln!(
+                    "syscall instructions of {:?} code are not analyzed",
+                    obj_file.architecture()
+                );
+            }
+        }
+        syscalls.sort();
+        syscalls.dedup();
+
+        Ok((syscalls, unresolved))
+    }
 
-        let fn_to_syscall_table = self.read_syscall_map()?;
+    fn run(&self) -> Result<(LinuxSyscall, Vec<ObjectSyscalls>)> {
+        let bin_path = match &self.rootfs {
+            Some(rootfs) => {
+                rootfs::resolve_in_root(rootfs, &self.target_bin).with_context(|| {
+                    format!(
+                        "{} is not found in {}",
+                        self.target_bin.display(),
+                        rootfs.display()
+                    )
+                })?
+            }
+            None => self.target_bin.clone(),
+        };
+        let bin_data = fs::read(&bin_path)?;
+        let link_info = LinkInfo::parse(&bin_data)?;
 
-        let syscalls = allow_syscall_list_from_symbols(
-            fnames.into_iter().map(String::from).collect(),
-            fn_to_syscall_table,
-        );
+        let fn_to_syscall_table = self.read_syscall_map(&link_info)?;
+
+        let (syscalls, unresolved_sites) =
+            self.analyze_object(&bin_data, &fn_to_syscall_table, true)?;
+        let mut objects = vec![ObjectSyscalls {
+            name: self.target_bin.display().to_string(),
+            path: Some(self.target_bin.clone()),
+            needed_by: None,
+            syscalls,
+            unresolved_sites,
+        }];
+
+        if let Some(rootfs) = &self.rootfs {
+            for library in rootfs::find_libraries(rootfs, &self.target_bin, link_info) {
+                let mut object = ObjectSyscalls {
+                    name: library.name,
+                    path: library.path,
+                    needed_by: Some(library.needed_by),
+                    ..Default::default()
+                };
+                match &library.host_path {
+                    Some(host_path) => {
+                        // the syscalls of libc are in the map, not all of its syscall sites are used
+                        let (syscalls, unresolved_sites) = self.analyze_object(
+                            &fs::read(host_path)?,
+                            &fn_to_syscall_table,
+                            !libc::is_libc(&object.name),
+                        )?;
+                        object.syscalls = syscalls;
+                        object.unresolved_sites = unresolved_sites;
+                    }
+                    None => eprintln!(
+                        "{} needed by {} is not found in {}",
+                        object.name,
+                        object.needed_by.as_deref().unwrap_or_default(),
+                        rootfs.display()
+                    ),
+                }
+                objects.push(object);
+            }
+        }
+
+        let mut syscalls: Vec<String> = objects
+            .iter()
+            .flat_map(|object| object.syscalls.iter().cloned())
+            .collect();
+        syscalls.sort();
+        syscalls.dedup();
 
         println!("{:?}", syscalls);
 
-        Ok(LinuxSyscall {
-            names: syscalls,
-            action: LinuxSeccompAction::SCMP_ACT_ALLOW,
-            args: None,
-        })
+        Ok((
+            LinuxSyscall {
+                names: syscalls,
+                action: LinuxSeccompAction::SCMP_ACT_ALLOW,
+                args: None,
+                errno_ret: None,
+            },
+            objects,
+        ))
     }
 
-    fn read_syscall_map(&self) -> Result<HashMap<String, Vec<String>>> {
-        let file = File::open(&self.syscall_map)?;
-        let map = serde_json::from_reader(file)
-            .with_context(|| format!("json deserialize error: {}", self.syscall_map.display()))?;
+    fn read_syscall_map(&self, link_info: &LinkInfo) -> Result<HashMap<String, Vec<String>>> {
+        let mut map = match libc::select_map(link_info) {
+            Some(bundled) => {
+                println!("using the bundled syscall map {}", bundled.name());
+                bundled.load()?
+            }
+            None => {
+                // the syscall instructions of a static binary are analyzed anyway
+                if self.syscall_map.is_none() {
+                    eprintln!(
+                        "libc of {} is not detected, pass a syscall map for its imports",
+                        self.target_bin.display()
+                    );
+                }
+                HashMap::new()
+            }
+        };
+
+        if let Some(path) = &self.syscall_map {
+            let file = File::open(path)?;
+            let extension: HashMap<String, Vec<String>> = serde_json::from_reader(file)
+                .with_context(|| format!("json deserialize error: {}", path.display()))?;
+            map.extend(extension);
+        }
 
         println!("{:?}", map);
 
@@ -90,12 +271,46 @@ impl CSeccompProfiler {
 mod test {
     use super::*;
     #[test]
-    fn test_allow_syscall_list_from_symbols() {
+    fn test_allow_syscall_list_from_imports() {
         let mut fn_to_syscall = HashMap::new();
         fn_to_syscall.insert("puts".to_string(), vec!["write".to_string()]);
-        let symbols = vec!["puts".to_string()];
+        fn_to_syscall.insert("stat".to_string(), vec!["stat".to_string()]);
+        fn_to_syscall.insert(
+            "stat@GLIBC_2.33".to_string(),
+            vec!["newfstatat".to_string()],
+        );
+        let imports = vec![
+            Import {
+                name: "puts".to_string(),
+                version: Some("GLIBC_2.2.5".to_string()),
+            },
+            Import {
+                name: "stat".to_string(),
+                version: Some("GLIBC_2.33".to_string()),
+            },
+        ];
+
+        let syscalls = allow_syscall_list_from_imports(&imports, &fn_to_syscall);
+        assert_eq!(
+            syscalls,
+            vec!["write".to_string(), "newfstatat".to_string()]
+        )
+    }
+
+    #[test]
+    fn test_allow_syscall_list_from_sites() {
+        let sites = vec![
+            SyscallSite {
+                address: 0x401000,
+                number: SyscallNumber::Constant(60),
+            },
+            SyscallSite {
+                address: 0x401010,
+                number: SyscallNumber::Unresolved("rax is computed"),
+            },
+        ];
 
-        let syscalls = allow_syscall_list_from_symbols(symbols, fn_to_syscall);
-        assert_eq!(syscalls, vec!["write".to_string()])
+        let syscalls = allow_syscall_list_from_sites(&sites);
+        assert_eq!(syscalls, vec!["exit".to_string()])
     }
 }
@@ -0,0 +1,171 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use anyhow::{bail, Result};
+use object::elf::{FileHeader32, FileHeader64};
+use object::read::elf::{FileHeader, SectionHeader};
+use object::{Endianness, FileKind, Object, ObjectSymbol, RelocationTarget};
+
+/// Mask of the version index in .gnu.version, the high bit marks hidden symbols
+const VERSYM_VERSION: u16 = 0x7fff;
+
+/// A symbol version required from a shared library, an entry of .gnu.version_r
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionNeed {
+    /// e.g. libc.so.6
+    pub file: String,
+    /// e.g. GLIBC_2.34
+    pub name: String,
+    /// Index referenced by .gnu.version
+    pub index: u16,
+}
+
+/// A function or object the binary uses from a shared library
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Import {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl fmt::Display for Import {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{}@{}", self.name, version),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+pub(crate) fn c_string(strings: &[u8], offset: u64) -> Option<String> {
+    let bytes = strings.get(offset as usize..)?;
+    let end = bytes.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+}
+
+/// Whether `data` is an ELF64 file rather than an ELF32 one
+pub(crate) fn is_elf64(data: &[u8]) -> Result<bool> {
+    match FileKind::parse(data)? {
+        FileKind::Elf32 => Ok(false),
+        FileKind::Elf64 => Ok(true),
+        kind => bail!("{:?} is not an ELF file", kind),
+    }
+}
+
+pub fn version_needs(data: &[u8]) -> Result<Vec<VersionNeed>> {
+    if is_elf64(data)? {
+        version_needs_of::<FileHeader64<Endianness>>(data)
+    } else {
+        version_needs_of::<FileHeader32<Endianness>>(data)
+    }
+}
+
+fn version_needs_of<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<Vec<VersionNeed>> {
+    let header = Elf::parse(data)?;
+    let endian = header.endian()?;
+    let sections = header.sections(endian, data)?;
+
+    let mut needs = vec![];
+    for section in sections.iter() {
+        let mut verneed = match section.gnu_verneed(endian, data)? {
+            Some(verneed) => verneed,
+            None => continue,
+        };
+        let strings = sections
+            .section(section.sh_link(endian) as usize)?
+            .data(endian, data)?;
+
+        while let Some((need, mut vernaux)) = verneed.next()? {
+            let file = c_string(strings, need.vn_file.get(endian).into()).unwrap_or_default();
+            while let Some(aux) = vernaux.next()? {
+                if let Some(name) = c_string(strings, aux.vna_name.get(endian).into()) {
+                    needs.push(VersionNeed {
+                        file: file.clone(),
+                        name,
+                        index: aux.vna_other.get(endian),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(needs)
+}
+
+/// Undefined dynamic symbols which are bound through the PLT or the GOT
+///
+/// Exports of the binary itself are not imports, and neither are undefined
+/// symbols which no relocation refers to.
+pub fn imports(data: &[u8]) -> Result<Vec<Import>> {
+    if is_elf64(data)? {
+        imports_of::<FileHeader64<Endianness>>(data)
+    } else {
+        imports_of::<FileHeader32<Endianness>>(data)
+    }
+}
+
+fn imports_of<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<Vec<Import>> {
+    let obj_file = object::File::parse(data)?;
+    let header = Elf::parse(data)?;
+    let endian = header.endian()?;
+    let sections = header.sections(endian, data)?;
+
+    let versions: HashMap<u16, String> = version_needs(data)?
+        .into_iter()
+        .map(|need| (need.index, need.name))
+        .collect();
+    let versym = sections.gnu_versym(endian, data)?.unwrap_or_default();
+
+    // R_X86_64_JUMP_SLOT for calls through the PLT, R_X86_64_GLOB_DAT for the GOT
+    let relocated: HashSet<_> = obj_file
+        .dynamic_relocations()
+        .into_iter()
+        .flatten()
+        .filter_map(|(_, relocation)| match relocation.target() {
+            RelocationTarget::Symbol(index) => Some(index),
+            _ => None,
+        })
+        .collect();
+
+    let mut imports: Vec<Import> = obj_file
+        .dynamic_symbols()
+        .filter(|symbol| symbol.is_undefined() && relocated.contains(&symbol.index()))
+        .filter_map(|symbol| {
+            let name = symbol.name().ok().filter(|name| !name.is_empty())?;
+            let version = versym
+                .get(symbol.index().0)
+                .and_then(|versym| versions.get(&(versym.0.get(endian) & VERSYM_VERSION)))
+                .cloned();
+            Some(Import {
+                name: name.to_string(),
+                version,
+            })
+        })
+        .collect();
+    imports.sort();
+    imports.dedup();
+
+    Ok(imports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn own_imports_are_versioned() {
+        let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let imports = imports(&data).unwrap();
+
+        if cfg!(target_env = "gnu") {
+            let write = imports
+                .iter()
+                .find(|import| import.name == "write")
+                .unwrap();
+            assert!(write.to_string().starts_with("write@GLIBC_2."));
+            // the test functions are defined in the binary
+            assert!(!imports
+                .iter()
+                .any(|import| import.name.contains("own_imports_are_versioned")));
+        }
+    }
+}
@@ -57,6 +57,7 @@ impl GoSeccompProfiler {
             names: syscalls,
             action: LinuxSeccompAction::SCMP_ACT_ALLOW,
             args: None,
+            errno_ret: None,
         })
     }
 }
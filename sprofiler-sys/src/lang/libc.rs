@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use anyhow::{bail, Context, Result};
+use object::elf;
+use object::elf::{FileHeader32, FileHeader64};
+use object::read::elf::{Dyn, FileHeader, SectionHeader};
+use object::{
+    Architecture, BinaryFormat, Endianness, Object, ObjectSection, ObjectSymbol, SymbolFlags,
+    SymbolKind,
+};
+
+use crate::arch::x86_64::call_graph::CallGraph;
+use crate::arch::x86_64::SYSCALLS;
+use crate::lang::elf::{c_string, is_elf64, version_needs};
+
+/// C library implementation a binary is linked with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+impl fmt::Display for Libc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Libc::Glibc => write!(f, "glibc"),
+            Libc::Musl => write!(f, "musl"),
+        }
+    }
+}
+
+/// Function-to-syscall map of a libc release on x86_64
+#[derive(Debug, PartialEq)]
+pub struct BundledMap {
+    pub libc: Libc,
+    pub version: (u32, u32),
+    json: &'static str,
+}
+
+/// Sorted by libc and version
+#[rustfmt::skip]
+const BUNDLED_MAPS: [BundledMap; 3] = [
+    BundledMap { libc: Libc::Glibc, version: (2, 31), json: include_str!("../../maps/glibc-2.31.json") },
+    BundledMap { libc: Libc::Glibc, version: (2, 35), json: include_str!("../../maps/glibc-2.35.json") },
+    BundledMap { libc: Libc::Musl,  version: (1, 2),  json: include_str!("../../maps/musl-1.2.json") },
+];
+
+impl BundledMap {
+    pub fn name(&self) -> String {
+        format!("{}-{}.{}", self.libc, self.version.0, self.version.1)
+    }
+
+    pub fn load(&self) -> Result<HashMap<String, Vec<String>>> {
+        serde_json::from_str(self.json)
+            .with_context(|| format!("json deserialize error: bundled map {}", self.name()))
+    }
+}
+
+/// How a binary is linked, from its program interpreter, dynamic section and symbols
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LinkInfo {
+    /// PT_INTERP
+    pub interpreter: Option<String>,
+    /// DT_NEEDED
+    pub needed: Vec<String>,
+    /// DT_RPATH, ignored by the loader if there is a DT_RUNPATH
+    pub rpath: Vec<String>,
+    /// DT_RUNPATH
+    pub runpath: Vec<String>,
+    /// Required symbol versions, e.g. GLIBC_2.34
+    pub versions: Vec<String>,
+    /// Libc identified by its internal symbols in a static binary
+    pub static_libc: Option<Libc>,
+}
+
+impl LinkInfo {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if is_elf64(data)? {
+            Self::parse_elf::<FileHeader64<Endianness>>(data)
+        } else {
+            Self::parse_elf::<FileHeader32<Endianness>>(data)
+        }
+    }
+
+    fn parse_elf<Elf: FileHeader<Endian = Endianness>>(data: &[u8]) -> Result<Self> {
+        let obj_file = object::File::parse(data)?;
+        let header = Elf::parse(data)?;
+        let endian = header.endian()?;
+        let sections = header.sections(endian, data)?;
+
+        let interpreter = obj_file
+            .section_by_name(".interp")
+            .and_then(|section| section.data().ok())
+            .and_then(|interp| c_string(interp, 0));
+
+        let mut needed = vec![];
+        let mut rpath = vec![];
+        let mut runpath = vec![];
+        for section in sections.iter() {
+            if section.sh_type(endian) != elf::SHT_DYNAMIC {
+                continue;
+            }
+            let strings = sections
+                .section(section.sh_link(endian) as usize)?
+                .data(endian, data)?;
+            let entries: &[Elf::Dyn] = section.data_as_array(endian, data)?;
+
+            for entry in entries {
+                let tag: u64 = entry.d_tag(endian).into();
+                let list = if tag == u64::from(elf::DT_NEEDED) {
+                    &mut needed
+                } else if tag == u64::from(elf::DT_RPATH) {
+                    &mut rpath
+                } else if tag == u64::from(elf::DT_RUNPATH) {
+                    &mut runpath
+                } else {
+                    continue;
+                };
+                if let Some(value) = c_string(strings, entry.d_val(endian).into()) {
+                    // search paths are separated by colons
+                    list.extend(value.split(':').map(String::from));
+                }
+            }
+        }
+
+        let static_libc = obj_file.symbols().find_map(|symbol| match symbol.name() {
+            Ok("__init_libc") => Some(Libc::Musl),
+            Ok("__libc_setup_tls") => Some(Libc::Glibc),
+            _ => None,
+        });
+
+        Ok(LinkInfo {
+            interpreter,
+            needed,
+            rpath,
+            runpath,
+            versions: version_needs(data)?
+                .into_iter()
+                .map(|need| need.name)
+                .collect(),
+            static_libc,
+        })
+    }
+
+    pub fn libc(&self) -> Option<Libc> {
+        let interpreter = self.interpreter.as_deref().unwrap_or_default();
+        if interpreter.contains("ld-musl") || self.needed.iter().any(|lib| lib.contains("musl")) {
+            Some(Libc::Musl)
+        } else if interpreter.contains("ld-linux")
+            || self.needed.iter().any(|lib| lib == "libc.so.6")
+            || self.glibc_version().is_some()
+        {
+            Some(Libc::Glibc)
+        } else {
+            self.static_libc
+        }
+    }
+
+    /// The newest GLIBC_x.y version required, the binary runs on this release or later
+    pub fn glibc_version(&self) -> Option<(u32, u32)> {
+        self.versions
+            .iter()
+            .filter_map(|version| {
+                let (major, minor) = version.strip_prefix("GLIBC_")?.split_once('.')?;
+                let minor = minor.split('.').next()?;
+                Some((major.parse().ok()?, minor.parse().ok()?))
+            })
+            .max()
+    }
+}
+
+/// The bundled map for the libc of a binary
+///
+/// This is the oldest release the binary can run on, or the newest release if
+/// the required version is unknown or newer than all bundled maps.
+pub fn select_map(info: &LinkInfo) -> Option<&'static BundledMap> {
+    let libc = info.libc()?;
+    let mut maps = BUNDLED_MAPS.iter().filter(|map| map.libc == libc);
+    let newest = maps.clone().next_back();
+
+    match (libc, info.glibc_version()) {
+        (Libc::Glibc, Some(required)) => maps.find(|map| map.version >= required).or(newest),
+        _ => newest,
+    }
+}
+
+/// Libraries of the C library and its loader, their syscalls are in the maps
+pub fn is_libc(name: &str) -> bool {
+    const PREFIXES: [&str; 8] = [
+        "libc.so",
+        "libc.musl",
+        "ld-linux",
+        "ld-musl",
+        "libpthread.so",
+        "libdl.so",
+        "librt.so",
+        "libm.so",
+    ];
+    PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+fn is_ifunc(symbol: &object::Symbol) -> bool {
+    matches!(symbol.flags(), SymbolFlags::Elf { st_info, .. } if st_info & 0xf == elf::STT_GNU_IFUNC)
+}
+
+/// Function-to-syscall map of a libc shared object, from the syscalls its exported functions reach
+pub fn generate_map(data: &[u8]) -> Result<BTreeMap<String, Vec<String>>> {
+    let obj_file = object::File::parse(data)?;
+    // the syscall numbers and the decoder are those of x86_64
+    if obj_file.format() != BinaryFormat::Elf
+        || obj_file.architecture() != Architecture::X86_64
+        || !obj_file.is_64()
+    {
+        bail!(
+            "{:?} {:?} is not supported, the libc must be an x86_64 ELF64 shared object",
+            obj_file.format(),
+            obj_file.architecture()
+        );
+    }
+    let graph = CallGraph::build(&obj_file)?;
+
+    let mut map = BTreeMap::new();
+    let mut unresolved = 0;
+    let mut ifuncs = vec![];
+    for symbol in obj_file
+        .dynamic_symbols()
+        .filter(|symbol| !symbol.is_undefined() && symbol.is_global())
+    {
+        let name = match symbol.name() {
+            Ok(name) if !name.is_empty() => name,
+            _ => continue,
+        };
+        // the address is that of the resolver which returns the implementation
+        if is_ifunc(&symbol) {
+            ifuncs.push(name);
+            continue;
+        }
+        if symbol.kind() != SymbolKind::Text {
+            continue;
+        }
+
+        let reachable = graph.reachable(symbol.address());
+        if reachable.unresolved > 0 {
+            unresolved += 1;
+        }
+        let syscalls: Vec<String> = reachable
+            .syscalls
+            .iter()
+            .filter_map(|number| SYSCALLS.get(&u32::try_from(*number).ok()?))
+            .map(|name| name.to_string())
+            .collect();
+        if !syscalls.is_empty() {
+            map.insert(name.to_string(), syscalls);
+        }
+    }
+
+    if unresolved > 0 {
+        eprintln!(
+            "{} functions reach syscall instructions with an unresolved number",
+            unresolved
+        );
+    }
+    if !ifuncs.is_empty() {
+        ifuncs.sort_unstable();
+        ifuncs.dedup();
+        eprintln!(
+            "{} IFUNC functions are unresolved, their implementation is selected at load time: {}",
+            ifuncs.len(),
+            ifuncs.join(", ")
+        );
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arch::x86_64;
+
+    #[test]
+    fn bundled_maps_have_known_syscalls() {
+        for map in BUNDLED_MAPS.iter() {
+            for (function, syscalls) in map.load().unwrap() {
+                for syscall in syscalls {
+                    assert!(
+                        x86_64::is_syscall(&syscall),
+                        "{}: {} of {}",
+                        map.name(),
+                        syscall,
+                        function
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn select_map_by_libc_and_version() {
+        let glibc = |version: &str| LinkInfo {
+            interpreter: Some("/lib64/ld-linux-x86-64.so.2".to_string()),
+            needed: vec!["libc.so.6".to_string()],
+            versions: vec!["GLIBC_2.2.5".to_string(), version.to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            select_map(&glibc("GLIBC_2.3.4")).unwrap().name(),
+            "glibc-2.31"
+        );
+        assert_eq!(
+            select_map(&glibc("GLIBC_2.34")).unwrap().name(),
+            "glibc-2.35"
+        );
+        assert_eq!(
+            select_map(&glibc("GLIBC_2.38")).unwrap().name(),
+            "glibc-2.35"
+        );
+
+        let musl = LinkInfo {
+            interpreter: Some("/lib/ld-musl-x86_64.so.1".to_string()),
+            needed: vec!["libc.musl-x86_64.so.1".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(select_map(&musl).unwrap().name(), "musl-1.2");
+
+        let musl_static = LinkInfo {
+            static_libc: Some(Libc::Musl),
+            ..Default::default()
+        };
+        assert_eq!(select_map(&musl_static).unwrap().name(), "musl-1.2");
+        assert_eq!(select_map(&LinkInfo::default()), None);
+    }
+
+    #[test]
+    fn generate_map_of_x86_64_only() {
+        let mut data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        // e_machine of the test binary, EM_AARCH64
+        data[18..20].copy_from_slice(&elf::EM_AARCH64.to_le_bytes());
+
+        let e = generate_map(&data).unwrap_err();
+        assert!(e.to_string().contains("Aarch64"));
+    }
+
+    #[test]
+    fn parse_own_link_info() {
+        let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+        let info = LinkInfo::parse(&data).unwrap();
+
+        if cfg!(target_env = "gnu") {
+            assert!(info.needed.iter().any(|lib| lib == "libc.so.6"));
+            assert!(info.glibc_version().is_some());
+            assert_eq!(info.libc(), Some(Libc::Glibc));
+        }
+    }
+
+    #[test]
+    fn parse_elf32_link_info() {
+        // an i386 executable header without sections
+        let mut data = vec![0; 52];
+        data[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', elf::ELFCLASS32, elf::ELFDATA2LSB, 1]);
+        data[16..18].copy_from_slice(&elf::ET_EXEC.to_le_bytes());
+        data[18..20].copy_from_slice(&elf::EM_386.to_le_bytes());
+        data[20..24].copy_from_slice(&1u32.to_le_bytes());
+        data[40..42].copy_from_slice(&52u16.to_le_bytes());
+
+        assert_eq!(LinkInfo::parse(&data).unwrap(), LinkInfo::default());
+        assert!(LinkInfo::parse(b"#!/bin/sh").is_err());
+    }
+}
@@ -5,7 +5,10 @@ use anyhow::Result;
 use oci_runtime_spec::LinuxSeccomp;
 
 pub mod c;
+pub mod elf;
 pub mod golang;
+pub mod libc;
+pub mod rootfs;
 
 pub trait SeccompProfiler {
     fn analyze(&self) -> Result<LinuxSeccomp>;
@@ -18,6 +21,7 @@ pub struct SeccompProfilerBuilder {
     dst: PathBuf,
     lang: Language,
     syscall_map: Option<PathBuf>,
+    rootfs: Option<PathBuf>,
 }
 
 impl SeccompProfilerBuilder {
@@ -27,6 +31,7 @@ impl SeccompProfilerBuilder {
             dst,
             lang,
             syscall_map: None,
+            rootfs: None,
         }
     }
 
@@ -35,19 +40,30 @@ impl SeccompProfilerBuilder {
         self
     }
 
+    /// Analyze `src` as a path in an image and its shared libraries
+    pub fn set_rootfs(&mut self, rootfs: PathBuf) -> &mut Self {
+        self.rootfs = Some(rootfs);
+        self
+    }
+
     pub fn build(&self) -> Box<dyn SeccompProfiler> {
         match self.lang {
             Language::C => Box::new(c::CSeccompProfiler {
                 target_bin: self.src.clone(),
                 destination: self.dst.clone(),
-                syscall_map: self
-                    .syscall_map
-                    .as_ref()
-                    .expect("C binary analyzer need syscall_map")
-                    .to_path_buf(),
+                syscall_map: self.syscall_map.clone(),
+                rootfs: self.rootfs.clone(),
             }),
             Language::Go => Box::new(golang::GoSeccompProfiler {
-                target_bin: self.src.clone(),
+                // Go binaries are linked statically, only the executable is analyzed
+                target_bin: match &self.rootfs {
+                    Some(rootfs) => {
+                        rootfs::resolve_in_root(rootfs, &self.src).unwrap_or_else(|| {
+                            rootfs.join(self.src.strip_prefix("/").unwrap_or(&self.src))
+                        })
+                    }
+                    None => self.src.clone(),
+                },
                 destination: self.dst.clone(),
             }),
         }
@@ -85,7 +101,6 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn build_c_seccomp_profiler_none_syscall_map() {
         SeccompProfilerBuilder::new(PathBuf::new(), PathBuf::new(), Language::C).build();
     }
@@ -0,0 +1,426 @@
+use std::collections::{HashSet, VecDeque};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::lang::libc::LinkInfo;
+
+/// Symlinks followed while resolving a path, as ELOOP in path_resolution(7)
+const MAX_SYMLINKS: usize = 40;
+
+/// Searched after the configured directories of glibc and musl
+const DEFAULT_LIBRARY_DIRS: [&str; 7] = [
+    "/lib/x86_64-linux-gnu",
+    "/usr/lib/x86_64-linux-gnu",
+    "/lib64",
+    "/usr/lib64",
+    "/lib",
+    "/usr/local/lib",
+    "/usr/lib",
+];
+
+/// Host path of `path` in `root`, symlinks are resolved as if `root` were `/`
+///
+/// Absolute link targets and `..` never leave the root, so the files of the
+/// host are not read when the image has links like /lib -> /usr/lib.
+pub fn resolve_in_root(root: &Path, path: &Path) -> Option<PathBuf> {
+    let mut resolved = PathBuf::from("/");
+    let mut pending: Vec<OsString> = path
+        .components()
+        .rev()
+        .map(|component| component.as_os_str().to_os_string())
+        .collect();
+    let mut links = 0;
+
+    while let Some(name) = pending.pop() {
+        match Path::new(&name).components().next() {
+            Some(Component::Normal(_)) => {}
+            Some(Component::ParentDir) => {
+                resolved.pop();
+                continue;
+            }
+            _ => continue,
+        }
+
+        let candidate = resolved.join(&name);
+        let host_path = root.join(candidate.strip_prefix("/").ok()?);
+        if !fs::symlink_metadata(&host_path)
+            .ok()?
+            .file_type()
+            .is_symlink()
+        {
+            resolved = candidate;
+            continue;
+        }
+
+        links += 1;
+        if links > MAX_SYMLINKS {
+            return None;
+        }
+        let target = fs::read_link(&host_path).ok()?;
+        if target.is_absolute() {
+            resolved = PathBuf::from("/");
+        }
+        pending.extend(
+            target
+                .components()
+                .rev()
+                .map(|component| component.as_os_str().to_os_string()),
+        );
+    }
+
+    Some(root.join(resolved.strip_prefix("/").ok()?))
+}
+
+/// Directories of /etc/ld.so.conf and the files it includes
+fn ld_so_conf_dirs(root: &Path, conf: &Path, depth: usize, dirs: &mut Vec<PathBuf>) {
+    let content = match resolve_in_root(root, conf).and_then(|path| fs::read_to_string(path).ok()) {
+        Some(content) => content,
+        None => return,
+    };
+
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let pattern = match line.strip_prefix("include") {
+            Some(pattern) if pattern.starts_with(char::is_whitespace) => pattern.trim(),
+            _ => {
+                if line.starts_with('/') {
+                    dirs.push(PathBuf::from(line));
+                }
+                continue;
+            }
+        };
+        if depth == 0 {
+            continue;
+        }
+
+        // relative includes are relative to /etc, the file name may contain a *
+        let pattern = Path::new("/etc").join(pattern);
+        let (dir, file_pattern) = match (pattern.parent(), pattern.file_name()) {
+            (Some(dir), Some(file_pattern)) => (dir, file_pattern.to_string_lossy()),
+            _ => continue,
+        };
+        let (prefix, suffix) = file_pattern.split_once('*').unwrap_or((&file_pattern, ""));
+        let mut confs: Vec<PathBuf> = resolve_in_root(root, dir)
+            .and_then(|host_dir| fs::read_dir(host_dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.ok()?.file_name().into_string().ok()?;
+                let matches = if file_pattern.contains('*') {
+                    name.len() >= prefix.len() + suffix.len()
+                        && name.starts_with(prefix)
+                        && name.ends_with(suffix)
+                } else {
+                    name == file_pattern
+                };
+                matches.then(|| dir.join(name))
+            })
+            .collect();
+        confs.sort();
+        for conf in confs {
+            ld_so_conf_dirs(root, &conf, depth - 1, dirs);
+        }
+    }
+}
+
+/// Library directories of the image searched after RPATH and RUNPATH
+pub fn system_library_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    ld_so_conf_dirs(root, Path::new("/etc/ld.so.conf"), 8, &mut dirs);
+
+    if let Some(musl_path) = resolve_in_root(root, Path::new("/etc/ld-musl-x86_64.path"))
+        .and_then(|path| fs::read_to_string(path).ok())
+    {
+        dirs.extend(
+            musl_path
+                .split(|c: char| c == ':' || c.is_whitespace())
+                .filter(|dir| dir.starts_with('/'))
+                .map(PathBuf::from),
+        );
+    }
+
+    dirs.extend(DEFAULT_LIBRARY_DIRS.iter().map(PathBuf::from));
+    let mut seen = HashSet::new();
+    dirs.retain(|dir| seen.insert(dir.clone()));
+    dirs
+}
+
+/// Expand $ORIGIN, the directory of the object in the image
+fn expand_origin(path: &str, origin: &Path) -> PathBuf {
+    let origin = origin.to_string_lossy();
+    PathBuf::from(
+        path.replace("${ORIGIN}", &origin)
+            .replace("$ORIGIN", &origin),
+    )
+}
+
+/// A shared library needed by the executable or by another library
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    /// DT_NEEDED name, e.g. libssl.so.3
+    pub name: String,
+    /// Name or path of the object which needs it
+    pub needed_by: String,
+    /// Path in the image, none if the library is not found
+    pub path: Option<PathBuf>,
+    /// Path on the host below the root
+    pub host_path: Option<PathBuf>,
+}
+
+/// Object whose DT_NEEDED entries are resolved
+#[derive(Clone)]
+struct Needer {
+    name: String,
+    /// Directory in the image for $ORIGIN
+    origin: PathBuf,
+    info: LinkInfo,
+}
+
+impl Needer {
+    fn expand(&self, dirs: &[String]) -> Vec<PathBuf> {
+        dirs.iter()
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| expand_origin(dir, &self.origin))
+            .collect()
+    }
+}
+
+/// Directories searched for the libraries of `needer` as ld.so(8) does
+fn search_dirs(needer: &Needer, executable: &Needer, system_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if needer.info.runpath.is_empty() {
+        dirs.extend(needer.expand(&needer.info.rpath));
+        // the RPATH of the executable applies to all libraries
+        if executable.info.runpath.is_empty() {
+            dirs.extend(executable.expand(&executable.info.rpath));
+        }
+    }
+    dirs.extend(needer.expand(&needer.info.runpath));
+    dirs.extend(system_dirs.iter().cloned());
+    dirs
+}
+
+/// Find the shared libraries of `executable` in `root`, transitively in load order
+///
+/// `executable` is a path in the image and `info` is how it is linked.
+pub fn find_libraries(root: &Path, executable: &Path, info: LinkInfo) -> Vec<Library> {
+    find_libraries_with(root, executable, info, |host_path| {
+        LinkInfo::parse(&fs::read(host_path).ok()?).ok()
+    })
+}
+
+/// `load` tells how the file at a host path is linked, None if it is not a library
+fn find_libraries_with<F>(root: &Path, executable: &Path, info: LinkInfo, load: F) -> Vec<Library>
+where
+    F: Fn(&Path) -> Option<LinkInfo>,
+{
+    let system_dirs = system_library_dirs(root);
+    let executable = Needer {
+        name: executable.display().to_string(),
+        origin: executable
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default(),
+        info,
+    };
+
+    let mut libraries = vec![];
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(executable.clone());
+
+    while let Some(needer) = queue.pop_front() {
+        let dirs = search_dirs(&needer, &executable, &system_dirs);
+
+        for name in &needer.info.needed {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+
+            // a name with a slash is a path, relative to the working directory of ld.so
+            let candidates = if name.contains('/') {
+                vec![expand_origin(name, &needer.origin)]
+            } else {
+                dirs.iter().map(|dir| dir.join(name)).collect()
+            };
+            let found = candidates.into_iter().find_map(|path| {
+                let host_path = resolve_in_root(root, &path)?;
+                let info = load(&host_path)?;
+                Some((path, host_path, info))
+            });
+
+            let (path, host_path) = match found {
+                Some((path, host_path, info)) => {
+                    queue.push_back(Needer {
+                        name: name.clone(),
+                        origin: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                        info,
+                    });
+                    (Some(path), Some(host_path))
+                }
+                None => (None, None),
+            };
+            libraries.push(Library {
+                name: name.clone(),
+                needed_by: needer.name.clone(),
+                path,
+                host_path,
+            });
+        }
+    }
+
+    libraries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn resolve_symlinks_in_root() {
+        let root = std::env::temp_dir().join(format!("sprofiler-rootfs-{}", std::process::id()));
+        fs::create_dir_all(root.join("usr/lib/x86_64-linux-gnu")).unwrap();
+        fs::create_dir_all(root.join("etc/ld.so.conf.d")).unwrap();
+        fs::write(root.join("usr/lib/x86_64-linux-gnu/libz.so.1.2.13"), "").unwrap();
+        symlink("/usr/lib", root.join("lib")).unwrap();
+        symlink(
+            "libz.so.1.2.13",
+            root.join("usr/lib/x86_64-linux-gnu/libz.so.1"),
+        )
+        .unwrap();
+        symlink("../../../../../etc", root.join("usr/lib/escape")).unwrap();
+        fs::write(
+            root.join("etc/ld.so.conf"),
+            "include /etc/ld.so.conf.d/*.conf\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("etc/ld.so.conf.d/x86_64-linux-gnu.conf"),
+            "# multiarch support\n/usr/local/lib/x86_64-linux-gnu\n/lib/x86_64-linux-gnu\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            resolve_in_root(&root, Path::new("/lib/x86_64-linux-gnu/libz.so.1")),
+            Some(root.join("usr/lib/x86_64-linux-gnu/libz.so.1.2.13"))
+        );
+        // .. stops at the root
+        assert_eq!(
+            resolve_in_root(&root, Path::new("/usr/lib/escape/ld.so.conf")),
+            Some(root.join("etc/ld.so.conf"))
+        );
+        assert_eq!(resolve_in_root(&root, Path::new("/lib/libssl.so.3")), None);
+
+        let dirs = system_library_dirs(&root);
+        assert_eq!(
+            dirs[..3],
+            [
+                PathBuf::from("/usr/local/lib/x86_64-linux-gnu"),
+                PathBuf::from("/lib/x86_64-linux-gnu"),
+                PathBuf::from("/usr/lib/x86_64-linux-gnu"),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn find_libraries_in_search_order() {
+        let root = std::env::temp_dir().join(format!("sprofiler-libraries-{}", std::process::id()));
+        let files = [
+            "app/bin/server",
+            "app/lib/libfoo.so.1",
+            "app/lib/libbar.so.1",
+            "app/lib/libbaz.so.1",
+            "app/lib/bar/libbar.so.1",
+            "opt/ignored/libbar.so.1",
+            "usr/lib/libfoo.so.1",
+            "usr/lib/libbaz.so.1",
+            "usr/lib/libqux.so.1.2",
+        ];
+        for file in files {
+            fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            fs::write(root.join(file), "").unwrap();
+        }
+        symlink("libqux.so.1.2", root.join("usr/lib/libqux.so.1")).unwrap();
+
+        let link_info = |needed: &[&str], rpath: &[&str], runpath: &[&str]| LinkInfo {
+            needed: needed.iter().map(|s| s.to_string()).collect(),
+            rpath: rpath.iter().map(|s| s.to_string()).collect(),
+            runpath: runpath.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let mut infos = HashMap::new();
+        // RUNPATH replaces its own RPATH and the RPATH of the executable
+        infos.insert(
+            root.join("app/lib/libfoo.so.1"),
+            link_info(&["libbar.so.1"], &["/opt/ignored"], &["$ORIGIN/bar"]),
+        );
+        // without RUNPATH, the RPATH of the executable is searched first
+        infos.insert(
+            root.join("usr/lib/libqux.so.1.2"),
+            link_info(&["libbaz.so.1"], &[], &[]),
+        );
+        for file in ["app/lib/bar/libbar.so.1", "app/lib/libbaz.so.1"] {
+            infos.insert(root.join(file), LinkInfo::default());
+        }
+        let executable = link_info(
+            &["libfoo.so.1", "libqux.so.1", "libmissing.so.1"],
+            &["$ORIGIN/../lib"],
+            &[],
+        );
+
+        let libraries = find_libraries_with(
+            &root,
+            Path::new("/app/bin/server"),
+            executable,
+            |host_path| infos.get(host_path).cloned(),
+        );
+        let breakdown: Vec<(&str, &str, Option<PathBuf>)> = libraries
+            .iter()
+            .map(|library| {
+                (
+                    library.name.as_str(),
+                    library.needed_by.as_str(),
+                    library.path.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            breakdown,
+            vec![
+                (
+                    "libfoo.so.1",
+                    "/app/bin/server",
+                    Some(PathBuf::from("/app/bin/../lib/libfoo.so.1"))
+                ),
+                (
+                    "libqux.so.1",
+                    "/app/bin/server",
+                    Some(PathBuf::from("/usr/lib/libqux.so.1"))
+                ),
+                ("libmissing.so.1", "/app/bin/server", None),
+                (
+                    "libbar.so.1",
+                    "libfoo.so.1",
+                    Some(PathBuf::from("/app/bin/../lib/bar/libbar.so.1"))
+                ),
+                (
+                    "libbaz.so.1",
+                    "libqux.so.1",
+                    Some(PathBuf::from("/app/bin/../lib/libbaz.so.1"))
+                ),
+            ]
+        );
+        assert_eq!(
+            libraries[1].host_path,
+            Some(root.join("usr/lib/libqux.so.1.2"))
+        );
+        assert_eq!(libraries[2].host_path, None);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
 pub mod arch;
 pub mod lang;
 pub mod oci;
+pub mod symbolize;
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use object::{Object, ObjectSegment, ObjectSymbol, SymbolKind};
+
+/// Executable mapping of a process, a line of /proc/<pid>/maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub start: u64,
+    pub end: u64,
+    /// File offset of `start`
+    pub offset: u64,
+    /// Path in the mount namespace of the process
+    pub path: PathBuf,
+}
+
+/// Executable file-backed mappings of /proc/<pid>/maps
+pub fn parse_maps(maps: &str) -> Vec<Mapping> {
+    maps.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || !fields[1].contains('x') || !fields[5].starts_with('/') {
+                return None;
+            }
+            let (start, end) = fields[0].split_once('-')?;
+            Some(Mapping {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+                offset: u64::from_str_radix(fields[2], 16).ok()?,
+                path: PathBuf::from(fields[5..].join(" ")),
+            })
+        })
+        .collect()
+}
+
+pub fn read_maps(pid: u32) -> Result<Vec<Mapping>> {
+    let path = format!("/proc/{}/maps", pid);
+    let maps = fs::read_to_string(&path).with_context(|| format!("failed to read {}", path))?;
+    Ok(parse_maps(&maps))
+}
+
+/// A symbolized instruction address
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub ip: u64,
+    pub object: Option<PathBuf>,
+    /// Function containing `ip` and the offset in it
+    pub symbol: Option<(String, u64)>,
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.symbol {
+            Some((name, offset)) => write!(f, "{}+{:#x}", name, offset)?,
+            None => write!(f, "{:#x}", self.ip)?,
+        }
+        if let Some(object) = &self.object {
+            write!(f, " ({})", object.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Loadable segments and function symbols of an ELF file
+#[derive(Debug, Default)]
+struct ObjectSymbols {
+    /// (file offset, file size, virtual address)
+    segments: Vec<(u64, u64, u64)>,
+    /// (virtual address, size, name), sorted by address
+    functions: Vec<(u64, u64, String)>,
+}
+
+impl ObjectSymbols {
+    fn parse(data: &[u8]) -> Result<Self> {
+        let obj_file = object::File::parse(data)?;
+
+        let segments = obj_file
+            .segments()
+            .map(|segment| {
+                let (offset, size) = segment.file_range();
+                (offset, size, segment.address())
+            })
+            .collect();
+
+        let mut functions: Vec<(u64, u64, String)> = obj_file
+            .symbols()
+            .chain(obj_file.dynamic_symbols())
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.is_definition())
+            .filter_map(|symbol| {
+                let name = symbol.name().ok()?;
+                Some((symbol.address(), symbol.size(), name.to_string()))
+            })
+            .collect();
+        functions.sort_unstable();
+        functions.dedup_by_key(|(address, _, _)| *address);
+
+        Ok(ObjectSymbols {
+            segments,
+            functions,
+        })
+    }
+
+    /// Virtual address of a file offset
+    fn address_of(&self, file_offset: u64) -> Option<u64> {
+        self.segments
+            .iter()
+            .find(|(offset, size, _)| *offset <= file_offset && file_offset < offset + size)
+            .map(|(offset, _, address)| address + (file_offset - offset))
+    }
+
+    fn lookup(&self, address: u64) -> Option<(String, u64)> {
+        let i = match self
+            .functions
+            .binary_search_by_key(&address, |(start, _, _)| *start)
+        {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let (start, size, name) = &self.functions[i];
+        // symbols without a size are trusted up to the next one
+        if *size > 0 && address >= start + size {
+            return None;
+        }
+        Some((name.clone(), address - start))
+    }
+}
+
+/// Resolves user-space addresses of processes, the parsed files are cached
+#[derive(Debug, Default)]
+pub struct Symbolizer {
+    /// Keyed by device and inode, containers may have different files on the same path
+    objects: HashMap<(u64, u64), Option<ObjectSymbols>>,
+}
+
+impl Symbolizer {
+    /// Resolve `ip` in `mappings` of `pid`, the files are read through /proc/<pid>/root
+    pub fn symbolize(&mut self, pid: u32, mappings: &[Mapping], ip: u64) -> Frame {
+        let mapping = match mappings
+            .iter()
+            .find(|mapping| mapping.start <= ip && ip < mapping.end)
+        {
+            Some(mapping) => mapping,
+            None => {
+                return Frame {
+                    ip,
+                    object: None,
+                    symbol: None,
+                }
+            }
+        };
+
+        let root = PathBuf::from(format!("/proc/{}/root", pid));
+        let file_offset = ip - mapping.start + mapping.offset;
+        let symbol = self
+            .load(&root.join(mapping.path.strip_prefix("/").unwrap_or(&mapping.path)))
+            .and_then(|symbols| {
+                let address = symbols.address_of(file_offset)?;
+                symbols.lookup(address)
+            });
+
+        Frame {
+            ip,
+            object: Some(mapping.path.clone()),
+            symbol,
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Option<&ObjectSymbols> {
+        let metadata = fs::metadata(path).ok()?;
+        self.objects
+            .entry((metadata.dev(), metadata.ino()))
+            .or_insert_with(|| {
+                let data = fs::read(path).ok()?;
+                ObjectSymbols::parse(&data).ok()
+            })
+            .as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_executable_mappings() {
+        let maps = "\
+55d0c0a00000-55d0c0a02000 r--p 00000000 fd:01 1234 /usr/bin/cat
+55d0c0a02000-55d0c0a07000 r-xp 00002000 fd:01 1234 /usr/bin/cat
+7f3a1c000000-7f3a1c021000 rw-p 00000000 00:00 0
+7f3a1c228000-7f3a1c3bd000 r-xp 00028000 fd:01 5678 /usr/lib/x86_64-linux-gnu/libc.so.6
+7ffd5e5f0000-7ffd5e5f2000 r-xp 00000000 00:00 0 [vdso]
+";
+        assert_eq!(
+            parse_maps(maps),
+            vec![
+                Mapping {
+                    start: 0x55d0c0a02000,
+                    end: 0x55d0c0a07000,
+                    offset: 0x2000,
+                    path: PathBuf::from("/usr/bin/cat"),
+                },
+                Mapping {
+                    start: 0x7f3a1c228000,
+                    end: 0x7f3a1c3bd000,
+                    offset: 0x28000,
+                    path: PathBuf::from("/usr/lib/x86_64-linux-gnu/libc.so.6"),
+                },
+            ]
+        );
+    }
+
+    #[inline(never)]
+    fn symbolize_target() -> u64 {
+        symbolize_target as *const () as u64
+    }
+
+    #[test]
+    fn symbolize_own_function() {
+        let pid = std::process::id();
+        let mappings = read_maps(pid).unwrap();
+        let ip = symbolize_target() + 1;
+
+        let frame = Symbolizer::default().symbolize(pid, &mappings, ip);
+
+        assert!(frame.to_string().contains("symbolize_target"));
+        let (name, offset) = frame.symbol.unwrap();
+        assert!(name.contains("symbolize_target"));
+        assert_eq!(offset, 1);
+    }
+}
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tracing::trace;
 
 #[derive(Builder, Clone, Default, Serialize, Deserialize)]
@@ -23,6 +24,8 @@ pub struct HookConf {
 pub struct Hook {
     pub path: PathBuf,
     pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub env: Vec<String>,
 }
 
 #[derive(Builder, Clone, Default, Serialize, Deserialize)]
@@ -42,7 +45,21 @@ pub enum Stages {
     PostStop,
 }
 
+/// sprofiler configuration which allows the profiles to be written below `hooks_dir`
+fn create_sprofiler_config(base_dir: &Path, hooks_dir: &Path) -> Result<PathBuf> {
+    let config_path = base_dir.join("sprofiler.json");
+    let file = File::create(&config_path)?;
+    serde_json::to_writer(file, &json!({ "outputRoot": hooks_dir }))?;
+    Ok(config_path)
+}
+
 pub fn create_hook_config(base_dir: PathBuf, sprofiler_path: PathBuf) -> Result<PathBuf> {
+    let hooks_dir = base_dir.join("hooks.d");
+    fs::create_dir_all(&hooks_dir)?;
+
+    let config_path = create_sprofiler_config(&base_dir, &hooks_dir)?;
+    let env = vec![format!("SPROFILER_CONFIG={}", config_path.display())];
+
     let annotation = {
         let mut a = HashMap::new();
         a.insert(
@@ -62,6 +79,7 @@ pub fn create_hook_config(base_dir: PathBuf, sprofiler_path: PathBuf) -> Result<
                     "dynamic".to_string(),
                     "start".to_string(),
                 ])
+                .env(env.clone())
                 .build()?,
         )
         .when(
@@ -82,15 +100,13 @@ pub fn create_hook_config(base_dir: PathBuf, sprofiler_path: PathBuf) -> Result<
                     "dynamic".to_string(),
                     "stop".to_string(),
                 ])
+                .env(env)
                 .build()?,
         )
         .when(WhenBuilder::default().annotations(annotation).build()?)
         .stages(vec![Stages::Poststop])
         .build()?;
 
-    let hooks_dir = base_dir.join("hooks.d");
-    fs::create_dir_all(&hooks_dir)?;
-
     let prestart_path = hooks_dir.join("sprofiler-prestart.json");
     let file = File::create(&prestart_path)?;
     serde_json::to_writer(file, &prestart)?;
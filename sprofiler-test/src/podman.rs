@@ -13,6 +13,8 @@ pub struct PodmanRunner {
     runtime: String,
     image: String,
     pub sprofiler_output: Option<PathBuf>,
+    #[builder(default)]
+    start_trigger: Option<String>,
     debug: bool,
     no_new_priv: bool,
 }
@@ -36,6 +38,11 @@ impl PodmanRunner {
 
         args.push(self.hooks_dir_arg());
         args.push(self.sprofiler_annotation());
+
+        if let Some(start_trigger) = self.start_trigger.as_ref() {
+            args.push(self.start_trigger_annotation(start_trigger));
+        }
+
         args.push(self.image.clone());
         args
     }
@@ -74,6 +81,10 @@ impl PodmanRunner {
         "--security-opt=no-new-privileges".to_string()
     }
 
+    fn start_trigger_annotation(&self, start_trigger: &str) -> String {
+        format!("--annotation=io.sprofiler.start_trigger={}", start_trigger)
+    }
+
     fn sprofiler_annotation(&self) -> String {
         if let Some(sprofiler_output) = self.sprofiler_output.as_ref() {
             format!(
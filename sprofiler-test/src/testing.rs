@@ -35,6 +35,11 @@ pub struct Test {
     pub runtime: String,
     pub should_success: bool,
     pub image: String,
+    #[serde(default)]
+    pub start_trigger: Option<String>,
+    /// Syscalls which must not be in the profile, e.g. made by the runtime before the start trigger
+    #[serde(default)]
+    pub excluded_syscalls: Vec<String>,
 }
 
 pub fn execute_test_case<P: AsRef<Path>>(
@@ -48,7 +53,12 @@ where
 {
     let seccomp_profile_path = seccomp::create_seccomp_profile(hooks_dir, &testcase.name)?;
 
-    let podman = PodmanRunnerBuilder::default()
+    let mut podman_builder = PodmanRunnerBuilder::default();
+    if let Some(start_trigger) = testcase.start_trigger.as_ref() {
+        podman_builder = podman_builder.start_trigger(start_trigger);
+    }
+
+    let podman = podman_builder
         .podman_path(podman_path)
         .runtime(&testcase.runtime)
         .hooks_dir(hooks_dir)
@@ -82,7 +92,12 @@ where
             let s = serde_json::to_string(&seccomp)?;
             trace!("[{}] Seccomp: {}", testcase.name, s);
 
+            assert_excluded_syscalls(&testcase.name, &seccomp, &testcase.excluded_syscalls);
             assert_seccomp_profile(&testcase.name, seccomp);
+
+            if let Some(start_trigger) = testcase.start_trigger.as_ref() {
+                assert_start_trigger(&testcase.name, seccomp_profile_path, start_trigger)?;
+            }
             return Ok(());
         }
     }
@@ -113,6 +128,35 @@ pub fn assert_seccomp_profile(testname: &str, seccomp: LinuxSeccomp) {
     }
 }
 
+pub fn assert_excluded_syscalls(testname: &str, seccomp: &LinuxSeccomp, excluded: &[String]) {
+    let names: Vec<&String> = seccomp
+        .syscalls
+        .iter()
+        .flatten()
+        .flat_map(|syscall| syscall.names.iter())
+        .collect();
+
+    for syscall in excluded {
+        assert!(!names.contains(&syscall));
+        info!("[{}] OK {} is not in seccomp.syscalls", testname, syscall);
+    }
+}
+
+pub fn assert_start_trigger(
+    testname: &str,
+    seccomp_profile_path: &Path,
+    start_trigger: &str,
+) -> Result<()> {
+    let file = File::open(seccomp_profile_path.with_extension("report.json"))?;
+    let report: serde_json::Value = serde_json::from_reader(file)?;
+    trace!("[{}] Report: {}", testname, report);
+
+    assert_eq!(report["startTrigger"], start_trigger);
+    info!("[{}] OK report.startTrigger == {}", testname, start_trigger);
+
+    Ok(())
+}
+
 pub fn run_tests(testing: Testing) -> Result<()> {
     if !testing.config.sprofiler.exists() {
         error!("Not found: {}", testing.config.sprofiler.display());
@@ -5,17 +5,82 @@
 #include <bpf/bpf_helpers.h>
 
 #define TASK_COMM_LEN 32
+#define MAX_SYSCALL_NR 512
+#define MAX_SYSCALL_ARGS 6
+#define MAX_AGGREGATE_ENTRIES 10240
+#define MAX_TARGET_CGROUPS 1024
+#define MAX_ERRNO 4095
+#define MAX_STACK_DEPTH 32
+#define MAX_CALL_SITES 4096
+#define MAX_CAPABILITY_CHECKS 4096
 
-const volatile u64 target_cgid = 51097;
+// cap_capable(9) options (include/linux/security.h)
+#define CAP_OPT_NOAUDIT 0x2
+
+// x86_64 syscall numbers
+#define NR_execve 59
+#define NR_prctl 157
+#define NR_seccomp 317
+#define NR_execveat 322
+
+#define PR_SET_SECCOMP 22
+#define PR_SET_NO_NEW_PRIVS 38
+
+// thread_info.status flag of a task in an ia32 syscall (arch/x86/include/asm/thread_info.h)
+#define TS_COMPAT 0x0002
+
+// bitmask of argument indexes to record, indexed by syscall number
+const volatile u8 arg_masks[MAX_SYSCALL_NR] = {};
+
+// count syscalls in syscall_counts instead of sending perf events
+const volatile bool aggregate = false;
 
 enum FILTER_STATE { Prestart, Running, Exited };
-volatile enum FILTER_STATE state = Prestart;
+
+enum START_TRIGGER {
+  // first prctl(2) of the runtime
+  TriggerPrctl,
+  // first execve(2), the container entrypoint
+  TriggerExecve,
+  // first seccomp(2), prctl(PR_SET_SECCOMP) or prctl(PR_SET_NO_NEW_PRIVS)
+  TriggerNoNewPrivs,
+  // every syscall since the cgroup is registered
+  TriggerCgroup,
+};
+
+struct filter_t {
+  u32 state;
+  u32 start_trigger;
+  // capture the user stack of the first call of each syscall
+  u32 call_sites;
+  // count the cap_capable(9) checks
+  u32 capabilities;
+};
+
+// syscalls which could not be stored in syscall_counts
+volatile u64 lost_aggregates = 0;
+
+// traced cgroup id -> filter, maintained by userspace
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_TARGET_CGROUPS);
+  __type(key, u64);
+  __type(value, struct filter_t);
+} target_cgroups SEC(".maps");
 
 struct sys_enter_event_t {
   uid_t uid;
+  // syscall_nr is an i386 syscall number
+  u32 compat;
   __u64 cgid;
   long syscall_nr;
   char comm[TASK_COMM_LEN];
+  unsigned long args[MAX_SYSCALL_ARGS];
+  // nanoseconds since boot
+  __u64 ts;
+  // tgid and pid of the kernel, pid and tid in userspace
+  u32 pid;
+  u32 tid;
 };
 
 struct {
@@ -24,51 +89,320 @@ struct {
   __uint(value_size, sizeof(u32));
 } sys_enter_events SEC(".maps");
 
-static __always_inline bool is_trace_target() {
-  u64 cgid = bpf_get_current_cgroup_id();
+struct syscall_key_t {
+  __u64 cgid;
+  long syscall_nr;
+  u32 compat;
+  u32 pad;
+  unsigned long args[MAX_SYSCALL_ARGS];
+};
 
-  if (target_cgid != 0 && cgid != target_cgid) {
-    return false;
+struct syscall_count_t {
+  __u64 count;
+  // nanoseconds since boot
+  __u64 first_seen;
+  __u64 last_seen;
+};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_AGGREGATE_ENTRIES);
+  __type(key, struct syscall_key_t);
+  __type(value, struct syscall_count_t);
+} syscall_counts SEC(".maps");
+
+struct syscall_result_key_t {
+  __u64 cgid;
+  long syscall_nr;
+  u32 compat;
+  u32 pad;
+};
+
+struct syscall_result_t {
+  __u64 successes;
+  __u64 failures;
+  __s64 last_errno;
+};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_AGGREGATE_ENTRIES);
+  __type(key, struct syscall_result_key_t);
+  __type(value, struct syscall_result_t);
+} syscall_results SEC(".maps");
+
+struct call_site_t {
+  u32 pid;
+  // number of entries in ips, the innermost frame first
+  u32 depth;
+  __u64 ips[MAX_STACK_DEPTH];
+};
+
+// first call of each syscall, keyed like syscall_results; userspace keeps the
+// entries until the cgroup is unregistered so that a syscall is captured once
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_CALL_SITES);
+  __type(key, struct syscall_result_key_t);
+  __type(value, struct call_site_t);
+} call_sites SEC(".maps");
+
+// call_site_t is too large for the BPF stack
+struct {
+  __uint(type, BPF_MAP_TYPE_PERCPU_ARRAY);
+  __uint(max_entries, 1);
+  __type(key, u32);
+  __type(value, struct call_site_t);
+} call_site_scratch SEC(".maps");
+
+struct capability_key_t {
+  __u64 cgid;
+  u32 cap;
+  u32 pad;
+};
+
+struct capability_check_t {
+  __u64 granted;
+  __u64 denied;
+};
+
+struct {
+  __uint(type, BPF_MAP_TYPE_HASH);
+  __uint(max_entries, MAX_CAPABILITY_CHECKS);
+  __type(key, struct capability_key_t);
+  __type(value, struct capability_check_t);
+} capability_checks SEC(".maps");
+
+static __always_inline struct filter_t *get_filter(u64 cgid) {
+  return bpf_map_lookup_elem(&target_cgroups, &cgid);
+}
+
+static __always_inline u32 is_compat_task(void) {
+  struct task_struct *task = (struct task_struct *)bpf_get_current_task();
+  return (BPF_CORE_READ(task, thread_info.status) & TS_COMPAT) != 0;
+}
+
+static __always_inline void read_args(struct trace_event_raw_sys_enter *ctx,
+                                      u32 compat, unsigned long *args) {
+  u8 mask = 0;
+  u64 nr = ctx->id;
+
+  // arg_masks is indexed by x86_64 syscall numbers
+  if (!compat && nr < MAX_SYSCALL_NR)
+    mask = arg_masks[nr];
+
+#pragma unroll
+  for (int i = 0; i < MAX_SYSCALL_ARGS; i++) {
+    if (mask & (1 << i))
+      args[i] = ctx->args[i];
   }
+}
+
+static __always_inline void count_syscall(struct trace_event_raw_sys_enter *ctx,
+                                          u64 cgid, u32 compat) {
+  struct syscall_key_t key = {};
+  u64 now = bpf_ktime_get_ns();
 
-  return true;
+  key.cgid = cgid;
+  key.syscall_nr = ctx->id;
+  key.compat = compat;
+  read_args(ctx, compat, key.args);
+
+  struct syscall_count_t *count = bpf_map_lookup_elem(&syscall_counts, &key);
+  if (count) {
+    __sync_fetch_and_add(&count->count, 1);
+    count->last_seen = now;
+    return;
+  }
+
+  struct syscall_count_t first = {
+      .count = 1,
+      .first_seen = now,
+      .last_seen = now,
+  };
+  if (bpf_map_update_elem(&syscall_counts, &key, &first, BPF_NOEXIST) != 0) {
+    // another CPU may have inserted the key in the meantime
+    count = bpf_map_lookup_elem(&syscall_counts, &key);
+    if (count) {
+      __sync_fetch_and_add(&count->count, 1);
+      count->last_seen = now;
+    } else {
+      __sync_fetch_and_add(&lost_aggregates, 1);
+    }
+  }
 }
 
-static __always_inline bool is_started(u64 nr) {
-  switch (state) {
+static __always_inline void capture_call_site(struct trace_event_raw_sys_enter *ctx,
+                                              u64 cgid, u32 compat) {
+  struct syscall_result_key_t key = {};
+  u32 zero = 0;
+
+  key.cgid = cgid;
+  key.syscall_nr = ctx->id;
+  key.compat = compat;
+
+  if (bpf_map_lookup_elem(&call_sites, &key))
+    return;
+
+  struct call_site_t *site = bpf_map_lookup_elem(&call_site_scratch, &zero);
+  if (!site)
+    return;
+
+  long size = bpf_get_stack(ctx, site->ips, sizeof(site->ips), BPF_F_USER_STACK);
+  if (size <= 0)
+    return;
+
+  site->pid = bpf_get_current_pid_tgid() >> 32;
+  site->depth = size / sizeof(site->ips[0]);
+  bpf_map_update_elem(&call_sites, &key, site, BPF_NOEXIST);
+}
+
+static __always_inline bool is_trigger(struct trace_event_raw_sys_enter *ctx,
+                                       u32 compat, u32 start_trigger) {
+  u64 nr = ctx->id;
+
+  // the trigger numbers are x86_64 syscalls called by the runtime
+  if (compat)
+    return start_trigger == TriggerCgroup;
+
+  switch (start_trigger) {
+  case TriggerPrctl:
+    return nr == NR_prctl;
+  case TriggerExecve:
+    return nr == NR_execve || nr == NR_execveat;
+  case TriggerNoNewPrivs:
+    return nr == NR_seccomp ||
+           (nr == NR_prctl && (ctx->args[0] == PR_SET_NO_NEW_PRIVS ||
+                               ctx->args[0] == PR_SET_SECCOMP));
+  case TriggerCgroup:
+    return true;
+  }
+  return false;
+}
+
+static __always_inline bool is_started(struct filter_t *filter,
+                                       struct trace_event_raw_sys_enter *ctx,
+                                       u32 compat) {
+  switch (filter->state) {
   case Prestart:
-    // SYS_prctl x86_64
-    if (nr == 157) {
-      state = Running;
-    }
-    return false;
+    if (!is_trigger(ctx, compat, filter->start_trigger))
+      return false;
+    filter->state = Running;
+    // the entrypoint execve(2) is filtered by the seccomp profile
+    return filter->start_trigger == TriggerExecve ||
+           filter->start_trigger == TriggerCgroup;
   case Running:
     return true;
   case Exited:
     return false;
   }
+  return false;
 }
 
 SEC("tracepoint/raw_syscalls/sys_enter")
 int tracepoint__raw_syscalls__sys_enter(struct trace_event_raw_sys_enter *ctx) {
+  u64 cgid = bpf_get_current_cgroup_id();
 
-  if (!is_trace_target())
+  struct filter_t *filter = get_filter(cgid);
+  if (!filter)
     return 0;
 
-  if (!is_started(ctx->id))
+  u32 compat = is_compat_task();
+
+  if (!is_started(filter, ctx, compat))
     return 0;
 
+  if (filter->call_sites)
+    capture_call_site(ctx, cgid, compat);
+
+  if (aggregate) {
+    count_syscall(ctx, cgid, compat);
+    return 0;
+  }
+
   struct sys_enter_event_t event = {};
+  u64 pid_tgid = bpf_get_current_pid_tgid();
 
+  event.ts = bpf_ktime_get_ns();
+  event.pid = pid_tgid >> 32;
+  event.tid = pid_tgid;
   event.uid = bpf_get_current_uid_gid();
-  event.cgid = bpf_get_current_cgroup_id();
+  event.compat = compat;
+  event.cgid = cgid;
   event.syscall_nr = ctx->id;
 
   bpf_get_current_comm(&event.comm, TASK_COMM_LEN);
+  read_args(ctx, compat, event.args);
 
   bpf_perf_event_output(ctx, &sys_enter_events, BPF_F_CURRENT_CPU, &event,
                         sizeof(event));
   return 0;
 }
 
+SEC("tracepoint/raw_syscalls/sys_exit")
+int tracepoint__raw_syscalls__sys_exit(struct trace_event_raw_sys_exit *ctx) {
+  u64 cgid = bpf_get_current_cgroup_id();
+
+  // the start trigger is only checked on sys_enter
+  struct filter_t *filter = get_filter(cgid);
+  if (!filter || filter->state != Running)
+    return 0;
+
+  struct syscall_result_key_t key = {};
+  key.cgid = cgid;
+  key.syscall_nr = ctx->id;
+  key.compat = is_compat_task();
+
+  struct syscall_result_t *result = bpf_map_lookup_elem(&syscall_results, &key);
+  if (!result) {
+    struct syscall_result_t zero = {};
+    bpf_map_update_elem(&syscall_results, &key, &zero, BPF_NOEXIST);
+    result = bpf_map_lookup_elem(&syscall_results, &key);
+    if (!result)
+      return 0;
+  }
+
+  if (ctx->ret < 0 && ctx->ret >= -MAX_ERRNO) {
+    __sync_fetch_and_add(&result->failures, 1);
+    result->last_errno = -ctx->ret;
+  } else {
+    __sync_fetch_and_add(&result->successes, 1);
+  }
+  return 0;
+}
+
+SEC("fexit/cap_capable")
+int BPF_PROG(fexit__cap_capable, const struct cred *cred,
+             struct user_namespace *targ_ns, int cap, unsigned int opts,
+             int ret) {
+  // the kernel probes some capabilities without needing them
+  if (opts & CAP_OPT_NOAUDIT)
+    return 0;
+
+  u64 cgid = bpf_get_current_cgroup_id();
+
+  struct filter_t *filter = get_filter(cgid);
+  if (!filter || filter->state != Running || !filter->capabilities)
+    return 0;
+
+  struct capability_key_t key = {};
+  key.cgid = cgid;
+  key.cap = cap;
+
+  struct capability_check_t *check = bpf_map_lookup_elem(&capability_checks, &key);
+  if (!check) {
+    struct capability_check_t zero = {};
+    bpf_map_update_elem(&capability_checks, &key, &zero, BPF_NOEXIST);
+    check = bpf_map_lookup_elem(&capability_checks, &key);
+    if (!check)
+      return 0;
+  }
+
+  if (ret == 0)
+    __sync_fetch_and_add(&check->granted, 1);
+  else
+    __sync_fetch_and_add(&check->denied, 1);
+  return 0;
+}
+
 char LICENSE[] SEC("license") = "GPL";
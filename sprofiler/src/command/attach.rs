@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+
+use crate::bpf::*;
+use crate::command::run::synthetic_state;
+use crate::command::tracer;
+use crate::dynamic::cgroup::ContainerCgroup;
+use crate::dynamic::options::{StartTrigger, TraceMode};
+use crate::dynamic::output::OutputOptions;
+
+/// Trace a running container or process from now on, until Ctrl-C or `timeout`
+pub fn attach_command(
+    container: Option<String>,
+    pid: Option<i32>,
+    output: PathBuf,
+    trace_mode: TraceMode,
+    timeout: Option<u64>,
+) -> Result<()> {
+    let (id, cgroup) = match (container, pid) {
+        (Some(id), None) => {
+            let cgroup = ContainerCgroup::find_container(&id)?;
+            (id, cgroup)
+        }
+        (None, Some(pid)) => (format!("pid-{}", pid), ContainerCgroup::from_pid(pid)?),
+        _ => bail!("either --container or --pid is required"),
+    };
+    println!("Attach to {} (cgroup: {})", id, cgroup.path.display());
+
+    // the container is already running, there is no start trigger to wait for
+    let state = synthetic_state(&id, &output, trace_mode, StartTrigger::Cgroup)?;
+
+    let skel_builder = SystraceSkelBuilder::default();
+    let mut systrace_skel = skel_builder.open()?;
+    tracer::configure_skel(&mut systrace_skel, trace_mode, false)?;
+
+    let mut skel = systrace_skel.load()?;
+
+    skel.attach()?;
+
+    tracer::register_cgroup(
+        &mut skel,
+        &cgroup,
+        &state,
+        trace_mode,
+        None,
+        None,
+        &OutputOptions::default(),
+    )?;
+
+    let (spinlock, _) = tracer::spawn_signal_handler()?;
+    let deadline = timeout.map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    tracer::poll_events(&mut skel, &spinlock, |skel| {
+        tracer::finish_targets(skel, trace_mode);
+        let exited = tracer::target_cgroups().is_empty();
+        if exited || matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+            spinlock.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    })?;
+
+    // the profile is already written if the container has exited
+    if tracer::target_cgroups().contains(&cgroup.id) {
+        tracer::unregister_target(&mut skel, cgroup.id, trace_mode)?;
+    }
+    println!(
+        "Detach from {}, profile written to {}",
+        id,
+        output.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+
+use anyhow::Result;
+use oci_runtime_spec::State;
+
+use crate::bpf::*;
+use crate::command::tracer;
+use crate::dynamic::annotation;
+use crate::dynamic::config;
+use crate::dynamic::control::{self, Request, Response};
+use crate::dynamic::options::TraceMode;
+use crate::dynamic::output::OutputOptions;
+
+/// Options of the daemon applied to every container
+struct DaemonOptions {
+    trace_mode: TraceMode,
+    /// cap_capable(9) is traced for the containers which ask for their capabilities
+    capabilities: bool,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+    output: OutputOptions,
+}
+
+fn handle_request(
+    skel: &mut SystraceSkel,
+    request: Request,
+    options: &DaemonOptions,
+) -> Result<Response> {
+    let trace_mode = options.trace_mode;
+
+    match request {
+        Request::Register { mut state } => {
+            if annotation::get_trace_mode(&state)? != trace_mode {
+                eprintln!(
+                    "{}: trace mode annotation is ignored, daemon runs in {:?} mode",
+                    state.id, trace_mode
+                );
+            }
+            if !options.capabilities && annotation::get_capabilities_path(&state).is_some() {
+                eprintln!(
+                    "{}: capabilities annotation is ignored, daemon runs without --capabilities",
+                    state.id
+                );
+                if let Some(annotations) = state.annotations.as_mut() {
+                    annotations.remove(annotation::SPROFILER_CAPABILITIES_ANNOTATION);
+                }
+            }
+            let cgid = tracer::register_target(
+                skel,
+                &state,
+                trace_mode,
+                options.checkpoint_interval,
+                options.max_duration,
+                &options.output,
+            )?;
+            println!("Register {} (cgroup: {})", state.id, cgid);
+            Ok(Response::Ok)
+        }
+        Request::Unregister { id } => {
+            let response = tracer::handle_target_request(
+                skel,
+                Request::Unregister { id: id.clone() },
+                trace_mode,
+            )?;
+            println!("Unregister {}", id);
+            Ok(response)
+        }
+        request => tracer::handle_target_request(skel, request, trace_mode),
+    }
+}
+
+/// Trace every registered container with a single BPF program
+pub fn daemon_command(
+    socket: &Path,
+    trace_mode: TraceMode,
+    capabilities: bool,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+) -> Result<()> {
+    let options = DaemonOptions {
+        trace_mode,
+        capabilities,
+        checkpoint_interval,
+        max_duration,
+        output: config::load()?.output_options()?,
+    };
+    let listener = control::bind_socket(socket)?;
+
+    let skel_builder = SystraceSkelBuilder::default();
+    let mut systrace_skel = skel_builder.open()?;
+    tracer::configure_skel(&mut systrace_skel, trace_mode, capabilities)?;
+
+    let mut skel = systrace_skel.load()?;
+
+    skel.attach()?;
+
+    let requests = control::spawn_listener(listener);
+    let (spinlock, steady_marker) = tracer::spawn_signal_handler()?;
+
+    println!("Listening on {}", socket.display());
+
+    tracer::poll_events(&mut skel, &spinlock, |skel| {
+        control::handle_requests(&requests, |request| handle_request(skel, request, &options));
+        tracer::finish_targets(skel, trace_mode);
+        tracer::update_phases(
+            skel,
+            trace_mode,
+            steady_marker.swap(false, Ordering::SeqCst),
+        );
+        tracer::checkpoint_targets(skel, trace_mode);
+        Ok(())
+    })?;
+
+    // write profiles of containers which are still running
+    for cgid in tracer::target_cgroups() {
+        if let Err(e) = tracer::unregister_target(&mut skel, cgid, trace_mode) {
+            eprintln!("cgroup {}: {:#}", cgid, e);
+        }
+    }
+
+    fs::remove_file(socket)?;
+
+    Ok(())
+}
+
+pub fn register_command(socket: &Path, state: &State) -> Result<()> {
+    control::send_request(
+        socket,
+        &Request::Register {
+            state: state.clone(),
+        },
+    )?;
+    Ok(())
+}
+
+pub fn unregister_command(socket: &Path, state: &State) -> Result<()> {
+    control::send_request(
+        socket,
+        &Request::Unregister {
+            id: state.id.clone(),
+        },
+    )?;
+    Ok(())
+}
@@ -1 +1,4 @@
+pub mod attach;
+pub mod daemon;
+pub mod run;
 pub mod tracer;
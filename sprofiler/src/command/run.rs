@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, ExitStatus};
+use std::sync::atomic::Ordering;
+
+use anyhow::{bail, Context, Result};
+use nix::unistd;
+use oci_runtime_spec::{State, Status};
+use serde::Serialize;
+
+use crate::bpf::*;
+use crate::command::tracer;
+use crate::dynamic::annotation;
+use crate::dynamic::cgroup::ContainerCgroup;
+use crate::dynamic::options::{StartTrigger, TraceMode};
+use crate::dynamic::output::OutputOptions;
+
+/// Annotation value of an option, the serde names are accepted by its FromStr
+fn annotation_value<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// State standing in for the OCI state of a container, so that processes
+/// outside of OCI hooks are traced and written like a container
+pub fn synthetic_state(
+    id: &str,
+    output: &Path,
+    trace_mode: TraceMode,
+    start_trigger: StartTrigger,
+) -> Result<State> {
+    let mut annotations = HashMap::new();
+    annotations.insert(
+        annotation::SPROFILER_OCI_ANNOTATION.to_string(),
+        output.to_string_lossy().to_string(),
+    );
+    annotations.insert(
+        annotation::SPROFILER_TRACE_MODE_ANNOTATION.to_string(),
+        annotation_value(&trace_mode),
+    );
+    annotations.insert(
+        annotation::SPROFILER_START_TRIGGER_ANNOTATION.to_string(),
+        annotation_value(&start_trigger),
+    );
+
+    Ok(State {
+        oci_version: String::new(),
+        id: id.to_string(),
+        status: Status::Running,
+        pid: None,
+        bundle: env::current_dir()?,
+        annotations: Some(annotations),
+        created: None,
+    })
+}
+
+/// Spawn `command` as a member of `cgroup`, it joins the cgroup before execve(2)
+fn spawn_in_cgroup(cgroup: &ContainerCgroup, command: &[String]) -> Result<process::Child> {
+    let procs = OpenOptions::new()
+        .write(true)
+        .open(cgroup.procs_path())
+        .with_context(|| format!("failed to open {}", cgroup.procs_path().display()))?;
+    let procs_fd = procs.as_raw_fd();
+
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+    unsafe {
+        // only async-signal-safe calls between fork(2) and execve(2)
+        cmd.pre_exec(move || {
+            unistd::write(procs_fd, b"0").map_err(std::io::Error::from)?;
+            Ok(())
+        });
+    }
+
+    let child = cmd
+        .spawn()
+        .with_context(|| format!("failed to spawn {}", command[0]))?;
+    drop(procs);
+
+    Ok(child)
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    }
+}
+
+/// Trace `command` in a transient cgroup and write its profile to `output` when it exits
+pub fn run_command(
+    output: PathBuf,
+    trace_mode: TraceMode,
+    start_trigger: StartTrigger,
+    command: Vec<String>,
+) -> Result<()> {
+    if command.is_empty() {
+        bail!("no command to run");
+    }
+
+    let id = format!("run-{}", process::id());
+    let state = synthetic_state(&id, &output, trace_mode, start_trigger)?;
+    let cgroup = ContainerCgroup::create_transient(&id)?;
+
+    let skel_builder = SystraceSkelBuilder::default();
+    let mut systrace_skel = skel_builder.open()?;
+    tracer::configure_skel(&mut systrace_skel, trace_mode, false)?;
+
+    let mut skel = systrace_skel.load()?;
+
+    skel.attach()?;
+
+    // the output path is given by the user running sprofiler, not by a container
+    tracer::register_cgroup(
+        &mut skel,
+        &cgroup,
+        &state,
+        trace_mode,
+        None,
+        None,
+        &OutputOptions::default(),
+    )?;
+
+    let (spinlock, _) = tracer::spawn_signal_handler()?;
+    let mut child = spawn_in_cgroup(&cgroup, &command)?;
+    let mut status = None;
+
+    tracer::poll_events(&mut skel, &spinlock, |_| {
+        status = child.try_wait()?;
+        if status.is_some() {
+            spinlock.store(false, Ordering::SeqCst);
+        }
+        Ok(())
+    })?;
+
+    tracer::unregister_target(&mut skel, cgroup.id, trace_mode)?;
+
+    // processes left behind by the command keep the cgroup busy
+    if let Err(e) = cgroup.remove() {
+        eprintln!("{:#}", e);
+    }
+
+    let status = match status {
+        Some(status) => status,
+        None => child.wait()?,
+    };
+    if !status.success() {
+        process::exit(exit_code(status));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn annotation_value_round_trip() {
+        for trace_mode in [TraceMode::PerfEvent, TraceMode::Aggregate] {
+            let value = annotation_value(&trace_mode);
+            assert_eq!(TraceMode::from_str(&value).unwrap(), trace_mode);
+        }
+        for start_trigger in [
+            StartTrigger::Prctl,
+            StartTrigger::Execve,
+            StartTrigger::NoNewPrivs,
+            StartTrigger::Cgroup,
+        ] {
+            let value = annotation_value(&start_trigger);
+            assert_eq!(StartTrigger::from_str(&value).unwrap(), start_trigger);
+        }
+    }
+}
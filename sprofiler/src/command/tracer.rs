@@ -1,54 +1,420 @@
-use std::collections::HashSet;
-use std::fs::{self, File};
-use std::io;
-use std::os::unix::fs::MetadataExt;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use lazy_static::lazy_static;
-use libbpf_rs::Error;
 use libbpf_rs::PerfBufferBuilder;
-use nix::sys::signal::{kill, Signal};
-use nix::unistd::Pid;
+use libbpf_rs::{Error, Map, MapFlags};
 use plain::Plain;
-use signal_hook::consts::*;
-use signal_hook::iterator::exfiltrator::WithOrigin;
-use signal_hook::iterator::SignalsInfo;
-use sprofiler_sys::arch::x86_64::SYSCALLS;
+use signal_hook::consts::{SIGUSR2, TERM_SIGNALS};
+use signal_hook::iterator::Signals;
 
 use crate::bpf::*;
 use crate::dynamic::annotation;
-use crate::dynamic::process;
+use crate::dynamic::args::{self, MAX_SYSCALL_ARGS};
+use crate::dynamic::call_site::CallSites;
+use crate::dynamic::capabilities::CapabilityRecorder;
+use crate::dynamic::cgroup::{self, ContainerCgroup};
+use crate::dynamic::config::Config;
+use crate::dynamic::control::{self, ControlCommand, Request, Response};
+use crate::dynamic::event_log::{EventLog, EventRecord};
+use crate::dynamic::options::{FailedSyscalls, StartTrigger, TraceMode};
+use crate::dynamic::output::{self, OutputOptions};
+use crate::dynamic::process_report::{self, ProcessBreakdown};
+use crate::dynamic::recorder::{self, SyscallRecorder, SyscallResult, SyscallStats};
+use crate::dynamic::report::{self, Phase, TraceReport};
 
-use oci_runtime_spec::{Arch, LinuxSeccomp, LinuxSeccompAction, LinuxSyscall, State};
+use oci_runtime_spec::State;
+
+/// Must match MAX_STACK_DEPTH in systrace.bpf.c
+const MAX_STACK_DEPTH: usize = 32;
+
+/// lost_aggregates of the BPF program when it was last reported
+static REPORTED_LOST_AGGREGATES: AtomicU64 = AtomicU64::new(0);
 
 lazy_static! {
-    static ref SYSCALL_LIST: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+    static ref TARGETS: Mutex<HashMap<u64, Target>> = Mutex::new(HashMap::new());
+    /// Containers unregistered by finish_targets, whose poststop hook may still come
+    static ref FINISHED: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Must match `enum FILTER_STATE` in systrace.bpf.c
+#[repr(u32)]
+#[allow(dead_code)]
+#[derive(Clone, Copy)]
+enum FilterState {
+    Prestart = 0,
+    Running = 1,
+    Exited = 2,
+}
+
+/// Value of the target_cgroups map, must match `struct filter_t` in systrace.bpf.c
+#[repr(C)]
+#[derive(Debug)]
+struct Filter {
+    pub state: u32,
+    pub start_trigger: u32,
+    pub call_sites: u32,
+    pub capabilities: u32,
+}
+
+/// Last values read from the counter maps of a target
+///
+/// BPF updates the entries at any time, so they are not deleted while the target is
+/// traced and only the increments since the last read are recorded.
+#[derive(Default)]
+struct Snapshots {
+    syscall_counts: HashMap<Vec<u8>, SyscallCountValue>,
+    syscall_results: HashMap<Vec<u8>, SyscallResultValue>,
+}
+
+/// Container traced by the BPF program
+struct Target {
+    state: State,
+    /// Output path of the seccomp profile, checked against the output root
+    profile_path: Option<PathBuf>,
+    output: OutputOptions,
+    cgroup: ContainerCgroup,
+    /// Processes were seen in the cgroup, it is finished once they are all gone
+    was_populated: bool,
+    /// Why tracing is over, the target is unregistered after the next poll so that
+    /// the events queued before are recorded
+    finishing: Option<String>,
+    /// The profile is written after this period since registration
+    max_duration: Option<Duration>,
+    start_trigger: StartTrigger,
+    failed_syscalls: FailedSyscalls,
+    checkpoint_interval: Option<Duration>,
+    last_checkpoint: Instant,
+    /// The steady state starts after this period since registration
+    startup_period: Option<Duration>,
+    registered_at: Instant,
+    startup: SyscallRecorder,
+    /// Syscalls after the startup phase, None while starting up
+    steady: Option<(Instant, SyscallRecorder)>,
+    /// Syscalls of each process, perf event mode only
+    processes: Option<ProcessBreakdown>,
+    /// Every observed event, perf event mode only
+    event_log: Option<EventLog>,
+    /// Stack of the first call of each syscall
+    call_sites: Option<CallSites>,
+    /// Capabilities checked by the container, with the path to write them to
+    capabilities: Option<(PathBuf, CapabilityRecorder)>,
+    snapshots: Snapshots,
+}
+
+impl Target {
+    fn is_checkpoint_due(&self, now: Instant) -> bool {
+        match self.checkpoint_interval {
+            Some(interval) => now.duration_since(self.last_checkpoint) >= interval,
+            None => false,
+        }
+    }
+
+    fn is_startup_over(&self, now: Instant) -> bool {
+        match self.startup_period {
+            Some(period) => now.duration_since(self.registered_at) >= period,
+            None => false,
+        }
+    }
+
+    /// Why tracing is over although the target was not unregistered
+    fn finish_reason(&mut self, now: Instant) -> Option<String> {
+        if matches!(self.max_duration, Some(max) if now.duration_since(self.registered_at) >= max) {
+            return Some("maximum tracing duration reached".to_string());
+        }
+
+        match self.cgroup.is_populated() {
+            Ok(true) => {
+                self.was_populated = true;
+                None
+            }
+            Ok(false) if self.was_populated || !self.cgroup.path.exists() => {
+                Some(format!("cgroup {} is gone", self.cgroup.path.display()))
+            }
+            Ok(false) => None,
+            Err(e) => {
+                eprintln!("{}: {:#}", self.state.id, e);
+                None
+            }
+        }
+    }
+
+    /// Syscalls of every phase
+    fn all_syscalls(&self) -> SyscallRecorder {
+        let mut recorder = self.startup.clone();
+        if let Some((_, steady)) = self.steady.as_ref() {
+            recorder.merge(steady);
+        }
+        recorder
+    }
+
+    /// Recorder of the current phase
+    fn recorder(&mut self) -> &mut SyscallRecorder {
+        match self.steady.as_mut() {
+            Some((_, steady)) => steady,
+            None => &mut self.startup,
+        }
+    }
+
+    /// Add the syscalls counted in BPF maps since the last collection to the recorder
+    /// of the current phase
+    fn collect(&mut self, skel: &mut SystraceSkel, cgid: u64, trace_mode: TraceMode) -> Result<()> {
+        let recorder = match self.steady.as_mut() {
+            Some((_, steady)) => steady,
+            None => &mut self.startup,
+        };
+        if trace_mode == TraceMode::Aggregate {
+            collect_syscall_counts(skel, cgid, &mut self.snapshots.syscall_counts, recorder)?;
+        }
+        collect_syscall_results(skel, cgid, &mut self.snapshots.syscall_results, recorder)?;
+        if let Some(call_sites) = self.call_sites.as_mut() {
+            collect_call_sites(skel, cgid, call_sites)?;
+        }
+        if let Some((_, capabilities)) = self.capabilities.as_mut() {
+            collect_capability_checks(skel, cgid, capabilities)?;
+        }
+        Ok(())
+    }
+
+    fn flush_event_log(&mut self) -> Result<()> {
+        match self.event_log.as_mut() {
+            Some(event_log) => event_log.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// Close the startup phase, later syscalls are recorded in the steady state
+    fn enter_steady_state(
+        &mut self,
+        skel: &mut SystraceSkel,
+        cgid: u64,
+        trace_mode: TraceMode,
+    ) -> Result<()> {
+        self.collect(skel, cgid, trace_mode)?;
+        self.steady = Some((Instant::now(), SyscallRecorder::default()));
+        Ok(())
+    }
+
+    /// Write the profile and the report, `checkpoint` is false for the final write
+    fn write_profile(&self, trace_mode: TraceMode, checkpoint: bool) -> Result<()> {
+        let path = match self.profile_path.as_ref() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let recorder = self.all_syscalls();
+        if let Some((_, steady)) = self.steady.as_ref() {
+            let phases = [
+                (Phase::Startup, &self.startup),
+                (Phase::SteadyState, steady),
+            ];
+            for (phase, phase_recorder) in phases {
+                let profile = phase_recorder.seccomp_profile(self.failed_syscalls);
+                output::write_json_atomic(
+                    &report::phase_profile_path(path, phase),
+                    &profile,
+                    &self.output,
+                )?;
+            }
+        }
+
+        let profile = recorder.seccomp_profile(self.failed_syscalls);
+        output::write_json_atomic(path, &profile, &self.output)?;
+
+        let frequencies = report::syscall_frequencies(&recorder.stats());
+        output::write_json_atomic(&report::stats_path(path), &frequencies, &self.output)?;
+
+        if let Some((capabilities_path, capabilities)) = self.capabilities.as_ref() {
+            output::write_json_atomic(
+                capabilities_path,
+                &capabilities.linux_capabilities(),
+                &self.output,
+            )?;
+        }
+
+        // aggregated syscalls do not carry comm and uid
+        if let (Some(processes), TraceMode::PerfEvent) = (self.processes.as_ref(), trace_mode) {
+            let process_report = processes.report();
+            output::write_json_atomic(
+                &report::process_report_path(path),
+                &process_report,
+                &self.output,
+            )?;
+        }
+
+        let trace_report = TraceReport {
+            container_id: self.state.id.clone(),
+            trace_mode,
+            start_trigger: self.start_trigger,
+            failed_syscalls_policy: self.failed_syscalls,
+            failed_syscalls: recorder.failed_syscalls(),
+            checkpoint,
+            steady_state_after: self
+                .steady
+                .as_ref()
+                .map(|(since, _)| since.duration_since(self.registered_at).as_secs()),
+            startup_only_syscalls: self
+                .steady
+                .as_ref()
+                .map(|(_, steady)| self.startup.difference(steady)),
+            call_sites: self.call_sites.as_ref().map(CallSites::report),
+            denied_capabilities: self
+                .capabilities
+                .as_ref()
+                .map(|(_, capabilities)| capabilities.denied()),
+            ia32_syscalls: recorder.compat_syscall_names(),
+        };
+        output::write_json_atomic(&report::report_path(path), &trace_report, &self.output)
+    }
 }
 
 #[repr(C)]
 #[derive(Default, Debug)]
 struct SysEnterEvent {
     pub uid: u32,
+    pub compat: u32,
     pub cgid: u64,
     pub syscall_nr: i64,
     pub comm: [u8; 32],
+    pub args: [u64; MAX_SYSCALL_ARGS],
+    pub ts: u64,
+    pub pid: u32,
+    pub tid: u32,
 }
 
 unsafe impl Plain for SysEnterEvent {}
 
+impl SysEnterEvent {
+    fn to_record(&self) -> EventRecord {
+        EventRecord {
+            ts: self.ts,
+            pid: self.pid,
+            tid: self.tid,
+            uid: self.uid,
+            comm: process_report::comm_to_string(&self.comm),
+            cgid: self.cgid,
+            nr: self.syscall_nr,
+            compat: self.compat != 0,
+            // only the arguments of argument-filtered syscalls are read
+            args: if self.args.iter().any(|arg| *arg != 0) {
+                Some(self.args)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Key of the syscall_counts map in aggregate mode
+#[repr(C)]
+#[derive(Default, Debug)]
+struct SyscallKey {
+    pub cgid: u64,
+    pub syscall_nr: i64,
+    pub compat: u32,
+    pub pad: u32,
+    pub args: [u64; MAX_SYSCALL_ARGS],
+}
+
+unsafe impl Plain for SyscallKey {}
+
+/// Value of the syscall_counts map, must match `struct syscall_count_t` in systrace.bpf.c
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+struct SyscallCountValue {
+    pub count: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+unsafe impl Plain for SyscallCountValue {}
+
+/// Key of the syscall_results map
+#[repr(C)]
+#[derive(Default, Debug)]
+struct SyscallResultKey {
+    pub cgid: u64,
+    pub syscall_nr: i64,
+    pub compat: u32,
+    pub pad: u32,
+}
+
+unsafe impl Plain for SyscallResultKey {}
+
+/// Value of the syscall_results map
+#[repr(C)]
+#[derive(Default, Debug, Clone, Copy)]
+struct SyscallResultValue {
+    pub successes: u64,
+    pub failures: u64,
+    pub last_errno: i64,
+}
+
+unsafe impl Plain for SyscallResultValue {}
+
+/// Key of the capability_checks map
+#[repr(C)]
+#[derive(Default, Debug)]
+struct CapabilityKey {
+    pub cgid: u64,
+    pub cap: u32,
+    pub pad: u32,
+}
+
+unsafe impl Plain for CapabilityKey {}
+
+/// Value of the capability_checks map
+#[repr(C)]
+#[derive(Default, Debug)]
+struct CapabilityCheck {
+    pub granted: u64,
+    pub denied: u64,
+}
+
+unsafe impl Plain for CapabilityCheck {}
+
+/// Value of the call_sites map, keyed by SyscallResultKey
+#[repr(C)]
+#[derive(Default, Debug)]
+struct CallSiteValue {
+    pub pid: u32,
+    pub depth: u32,
+    pub ips: [u64; MAX_STACK_DEPTH],
+}
+
+unsafe impl Plain for CallSiteValue {}
+
 fn handle_event(_cpu: i32, data: &[u8]) {
     let mut event = SysEnterEvent::default();
     plain::copy_from_bytes(&mut event, data).expect("Data buffer was too short or invalid");
 
-    let syscall_name = SYSCALLS.get(&(event.syscall_nr as u32));
+    let mut targets = TARGETS.lock().unwrap();
+    if let Some(target) = targets.get_mut(&event.cgid) {
+        let recorder = target.recorder();
+        recorder.record(event.syscall_nr, event.compat != 0, &event.args);
+        recorder.record_stats(
+            event.syscall_nr,
+            event.compat != 0,
+            &SyscallStats::at(event.ts),
+        );
 
-    if let Some(syscall_name) = syscall_name {
-        let mut syscall_list = SYSCALL_LIST.lock().unwrap();
-        syscall_list.insert(syscall_name);
+        if let Some(processes) = target.processes.as_mut() {
+            processes.record(&event.comm, event.uid, event.syscall_nr, event.compat != 0);
+        }
+
+        if let Some(event_log) = target.event_log.as_mut() {
+            if let Err(e) = event_log.write(&event.to_record()) {
+                eprintln!("event log of {}: {:#}", target.state.id, e);
+                target.event_log = None;
+            }
+        }
     }
 }
 
@@ -56,50 +422,476 @@ fn handle_lost_event(cpu: i32, count: u64) {
     eprintln!("Lost event (CPU: {}, COUNT: {})", cpu, count);
 }
 
-fn gen_seccomp_rule() -> anyhow::Result<LinuxSeccomp> {
-    let syscall_list = SYSCALL_LIST.lock().unwrap();
-    let mut syscall_list: Vec<String> = syscall_list
-        .clone()
-        .into_iter()
-        .map(|s| s.to_string())
+/// Add the syscalls of `cgid` aggregated since `last` in the syscall_counts map to `recorder`
+fn collect_syscall_counts(
+    skel: &mut SystraceSkel,
+    cgid: u64,
+    last: &mut HashMap<Vec<u8>, SyscallCountValue>,
+    recorder: &mut SyscallRecorder,
+) -> Result<()> {
+    let keys: Vec<Vec<u8>> = skel.maps().syscall_counts().keys().collect();
+
+    for key in keys {
+        let mut syscall_key = SyscallKey::default();
+        plain::copy_from_bytes(&mut syscall_key, &key)
+            .expect("Key buffer was too short or invalid");
+
+        if syscall_key.cgid != cgid {
+            continue;
+        }
+
+        let value = match skel.maps().syscall_counts().lookup(&key, MapFlags::ANY)? {
+            Some(value) => value,
+            None => continue,
+        };
+        let mut count = SyscallCountValue::default();
+        plain::copy_from_bytes(&mut count, &value).expect("Value buffer was too short or invalid");
+
+        let previous = last.insert(key, count).unwrap_or_default();
+        let calls = count.count.saturating_sub(previous.count);
+        if calls == 0 {
+            continue;
+        }
+        recorder.record(
+            syscall_key.syscall_nr,
+            syscall_key.compat != 0,
+            &syscall_key.args,
+        );
+        recorder.record_stats(
+            syscall_key.syscall_nr,
+            syscall_key.compat != 0,
+            &SyscallStats {
+                count: calls,
+                first_seen: count.first_seen,
+                last_seen: count.last_seen,
+            },
+        );
+    }
+
+    // the BPF counter is shared by every cgroup and never reset
+    let lost = skel.bss().lost_aggregates;
+    let reported = REPORTED_LOST_AGGREGATES.swap(lost, Ordering::SeqCst);
+    if lost > reported {
+        eprintln!("Lost aggregated syscall (COUNT: {})", lost - reported);
+    }
+
+    Ok(())
+}
+
+/// Add the syscall results of `cgid` since `last` in the syscall_results map to `recorder`
+fn collect_syscall_results(
+    skel: &mut SystraceSkel,
+    cgid: u64,
+    last: &mut HashMap<Vec<u8>, SyscallResultValue>,
+    recorder: &mut SyscallRecorder,
+) -> Result<()> {
+    let keys: Vec<Vec<u8>> = skel.maps().syscall_results().keys().collect();
+
+    for key in keys {
+        let mut result_key = SyscallResultKey::default();
+        plain::copy_from_bytes(&mut result_key, &key).expect("Key buffer was too short or invalid");
+
+        if result_key.cgid != cgid {
+            continue;
+        }
+
+        let value = match skel.maps().syscall_results().lookup(&key, MapFlags::ANY)? {
+            Some(value) => value,
+            None => continue,
+        };
+        let mut result = SyscallResultValue::default();
+        plain::copy_from_bytes(&mut result, &value).expect("Value buffer was too short or invalid");
+
+        let previous = last.insert(key, result).unwrap_or_default();
+        let successes = result.successes.saturating_sub(previous.successes);
+        let failures = result.failures.saturating_sub(previous.failures);
+        if successes == 0 && failures == 0 {
+            continue;
+        }
+        recorder.record_result(
+            result_key.syscall_nr,
+            result_key.compat != 0,
+            &SyscallResult {
+                successes,
+                failures,
+                errno: result.last_errno as u32,
+            },
+        );
+    }
+
+    Ok(())
+}
+
+/// Record the capability checks of `cgid` in the capability_checks map, which only
+/// tell whether a capability was ever granted or denied
+fn collect_capability_checks(
+    skel: &mut SystraceSkel,
+    cgid: u64,
+    recorder: &mut CapabilityRecorder,
+) -> Result<()> {
+    let keys: Vec<Vec<u8>> = skel.maps().capability_checks().keys().collect();
+
+    for key in keys {
+        let mut capability_key = CapabilityKey::default();
+        plain::copy_from_bytes(&mut capability_key, &key)
+            .expect("Key buffer was too short or invalid");
+
+        if capability_key.cgid != cgid {
+            continue;
+        }
+
+        let value = match skel
+            .maps()
+            .capability_checks()
+            .lookup(&key, MapFlags::ANY)?
+        {
+            Some(value) => value,
+            None => continue,
+        };
+        let mut check = CapabilityCheck::default();
+        plain::copy_from_bytes(&mut check, &value).expect("Value buffer was too short or invalid");
+
+        recorder.record(capability_key.cap, check.granted, check.denied);
+    }
+
+    Ok(())
+}
+
+/// Symbolize the new call sites of `cgid` in the call_sites map
+///
+/// The entries are kept so that BPF does not capture the same syscall again.
+fn collect_call_sites(
+    skel: &mut SystraceSkel,
+    cgid: u64,
+    call_sites: &mut CallSites,
+) -> Result<()> {
+    let keys: Vec<Vec<u8>> = skel.maps().call_sites().keys().collect();
+
+    for key in keys {
+        let mut site_key = SyscallResultKey::default();
+        plain::copy_from_bytes(&mut site_key, &key).expect("Key buffer was too short or invalid");
+
+        if site_key.cgid != cgid {
+            continue;
+        }
+        let syscall = match recorder::syscall_name(site_key.syscall_nr, site_key.compat != 0) {
+            Some(syscall) if !call_sites.contains(syscall) => syscall,
+            _ => continue,
+        };
+
+        let value = match skel.maps().call_sites().lookup(&key, MapFlags::ANY)? {
+            Some(value) => value,
+            None => continue,
+        };
+        let mut site = CallSiteValue::default();
+        plain::copy_from_bytes(&mut site, &value).expect("Value buffer was too short or invalid");
+
+        let depth = (site.depth as usize).min(MAX_STACK_DEPTH);
+        call_sites.record(syscall, site.pid, &site.ips[..depth]);
+    }
+
+    Ok(())
+}
+
+/// Symbolize the call sites of every target, soon enough for the callers to be alive
+fn collect_all_call_sites(skel: &mut SystraceSkel) {
+    let mut targets = TARGETS.lock().unwrap();
+
+    for (cgid, target) in targets.iter_mut() {
+        if let Some(call_sites) = target.call_sites.as_mut() {
+            if let Err(e) = collect_call_sites(skel, *cgid, call_sites) {
+                eprintln!("call sites of {}: {:#}", target.state.id, e);
+            }
+        }
+    }
+}
+
+/// Delete the entries of `cgid` from a map whose keys start with the cgroup id
+///
+/// A leftover entry only takes space in the map, so failures are logged.
+fn remove_entries(map: &mut Map, cgid: u64) {
+    let keys: Vec<Vec<u8>> = map
+        .keys()
+        .filter(|key| key.starts_with(&cgid.to_ne_bytes()))
         .collect();
-    syscall_list.sort();
-
-    let seccomp_profile = LinuxSeccomp {
-        syscalls: Some(vec![LinuxSyscall {
-            names: syscall_list,
-            action: LinuxSeccompAction::SCMP_ACT_ALLOW,
-            args: None,
-        }]),
-        default_action: LinuxSeccompAction::SCMP_ACT_ERRNO,
-        architectures: Some(vec![Arch::SCMP_ARCH_X86_64]),
+
+    for key in keys {
+        if let Err(e) = map.delete(&key) {
+            eprintln!("cgroup {}: failed to delete a map entry: {}", cgid, e);
+        }
+    }
+}
+
+/// `capabilities` loads the cap_capable(9) program, which needs fexit support of the kernel
+pub fn configure_skel(
+    open_skel: &mut OpenSystraceSkel,
+    trace_mode: TraceMode,
+    capabilities: bool,
+) -> Result<()> {
+    open_skel.rodata().arg_masks = args::arg_masks();
+    open_skel.rodata().aggregate = trace_mode == TraceMode::Aggregate;
+    // the skeleton attaches only the programs which are loaded
+    open_skel
+        .progs_mut()
+        .fexit__cap_capable()
+        .set_autoload(capabilities)?;
+    Ok(())
+}
+
+/// Start tracing the cgroup of the container, returns the cgroup id
+///
+/// `checkpoint_interval` and `max_duration` are used unless the container has its own
+/// annotations.
+pub fn register_target(
+    skel: &mut SystraceSkel,
+    state: &State,
+    trace_mode: TraceMode,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+    output: &OutputOptions,
+) -> Result<u64> {
+    let cgroup = cgroup::resolve(state)?;
+    register_cgroup(
+        skel,
+        &cgroup,
+        state,
+        trace_mode,
+        checkpoint_interval,
+        max_duration,
+        output,
+    )?;
+    Ok(cgroup.id)
+}
+
+/// Start tracing `cgroup`, the profile is configured by the annotations of `state`
+///
+/// The output paths of the annotations are checked against the root of `output`.
+pub fn register_cgroup(
+    skel: &mut SystraceSkel,
+    cgroup: &ContainerCgroup,
+    state: &State,
+    trace_mode: TraceMode,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+    output: &OutputOptions,
+) -> Result<()> {
+    let cgid = cgroup.id;
+    let profile_path = annotation::get_trace_target_path(state)
+        .map(|path| output.resolve(&path))
+        .transpose()?;
+    let start_trigger = annotation::get_start_trigger(state)?;
+    let failed_syscalls = annotation::get_failed_syscalls(state)?;
+    let checkpoint_interval = annotation::get_checkpoint_interval(state)?
+        .or(checkpoint_interval)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    let startup_period = annotation::get_startup_period(state)?.map(Duration::from_secs);
+    let max_duration = annotation::get_max_duration(state)?
+        .or(max_duration)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs);
+    // only perf events tell which process made a syscall
+    let processes = if !annotation::get_process_report(state)? {
+        None
+    } else if trace_mode == TraceMode::Aggregate {
+        eprintln!(
+            "{}: process report annotation is ignored in {:?} mode",
+            state.id, trace_mode
+        );
+        None
+    } else {
+        Some(ProcessBreakdown::default())
+    };
+    let event_log = match profile_path.as_ref() {
+        // an empty log would rebuild an empty profile
+        Some(_) if trace_mode == TraceMode::Aggregate && annotation::get_event_log(state)? => {
+            eprintln!(
+                "{}: event log annotation is ignored in {:?} mode",
+                state.id, trace_mode
+            );
+            None
+        }
+        Some(path) if annotation::get_event_log(state)? => {
+            Some(EventLog::create(&report::event_log_path(path), output)?)
+        }
+        _ => None,
+    };
+    let call_sites = if annotation::get_call_sites(state)? {
+        Some(CallSites::default())
+    } else {
+        None
+    };
+    let capabilities = match annotation::get_capabilities_path(state) {
+        Some(path) => Some((output.resolve(&path)?, CapabilityRecorder::default())),
+        None => None,
+    };
+
+    let filter_state = match start_trigger {
+        StartTrigger::Cgroup => FilterState::Running,
+        _ => FilterState::Prestart,
+    };
+    let filter = Filter {
+        state: filter_state as u32,
+        start_trigger: start_trigger as u32,
+        call_sites: call_sites.is_some() as u32,
+        capabilities: capabilities.is_some() as u32,
+    };
+    skel.maps_mut().target_cgroups().update(
+        &cgid.to_ne_bytes(),
+        unsafe { plain::as_bytes(&filter) },
+        MapFlags::ANY,
+    )?;
+
+    let target = Target {
+        state: state.clone(),
+        profile_path,
+        output: output.clone(),
+        cgroup: cgroup.clone(),
+        was_populated: false,
+        finishing: None,
+        max_duration,
+        start_trigger,
+        failed_syscalls,
+        checkpoint_interval,
+        last_checkpoint: Instant::now(),
+        startup_period,
+        registered_at: Instant::now(),
+        startup: SyscallRecorder::default(),
+        steady: None,
+        processes,
+        event_log,
+        call_sites,
+        capabilities,
+        snapshots: Snapshots::default(),
+    };
+    TARGETS.lock().unwrap().insert(cgid, target);
+    FINISHED.lock().unwrap().remove(&state.id);
+
+    Ok(())
+}
+
+/// Stop tracing the cgroup and write the profile of its container
+///
+/// The target stays registered until its profile is written, so a failed
+/// unregister can be retried.
+pub fn unregister_target(skel: &mut SystraceSkel, cgid: u64, trace_mode: TraceMode) -> Result<()> {
+    let mut targets = TARGETS.lock().unwrap();
+    let target = targets
+        .get_mut(&cgid)
+        .with_context(|| format!("cgroup {} is not traced", cgid))?;
+
+    // BPF no longer updates the entries of the cgroup, a retry finds no filter
+    match skel.maps_mut().target_cgroups().delete(&cgid.to_ne_bytes()) {
+        Ok(()) | Err(Error::System(2)) => {} // ENOENT
+        Err(e) => return Err(e.into()),
     };
 
-    Ok(seccomp_profile)
+    target.collect(skel, cgid, trace_mode)?;
+    target.flush_event_log()?;
+    target.write_profile(trace_mode, false)?;
+    targets.remove(&cgid);
+    drop(targets);
+
+    remove_entries(skel.maps_mut().syscall_counts(), cgid);
+    remove_entries(skel.maps_mut().syscall_results(), cgid);
+    remove_entries(skel.maps_mut().capability_checks(), cgid);
+    remove_entries(skel.maps_mut().call_sites(), cgid);
+
+    Ok(())
 }
 
-/// only work when cgroup driver is systemd & v2 on podman
-fn get_contianer_cgroup_id(container_id: &str) -> Result<u64> {
-    let path = format!(
-        "/sys/fs/cgroup/machine.slice/libpod-{}.scope/container",
-        container_id
-    );
-    let path = PathBuf::from(path);
-    let meta = fs::metadata(&path)
-        .with_context(|| format!("failed to get metadata from {}", path.display()))?;
-    Ok(meta.ino())
+/// Start the steady state of the targets whose startup period has elapsed, or
+/// of every target when `marked` (SIGUSR2)
+pub fn update_phases(skel: &mut SystraceSkel, trace_mode: TraceMode, marked: bool) {
+    let now = Instant::now();
+    let mut targets = TARGETS.lock().unwrap();
+
+    for (cgid, target) in targets.iter_mut() {
+        if target.steady.is_some() || !(marked || target.is_startup_over(now)) {
+            continue;
+        }
+        match target.enter_steady_state(skel, *cgid, trace_mode) {
+            Ok(()) => println!("{}: steady state", target.state.id),
+            Err(e) => eprintln!("steady state {}: {:#}", target.state.id, e),
+        }
+    }
 }
 
-fn start_tracing(spinlock: Arc<AtomicBool>, state: &State) -> Result<()> {
-    let skel_builder = SystraceSkelBuilder::default();
-    let mut systrace_skel = skel_builder.open()?;
+/// Write the profiles of the targets whose cgroup is gone or whose maximum tracing
+/// duration has elapsed, in case the poststop hook never runs
+///
+/// A target is unregistered on the tick after it is found to be finished, once the
+/// perf buffer was polled again.
+pub fn finish_targets(skel: &mut SystraceSkel, trace_mode: TraceMode) {
+    let now = Instant::now();
+    let finished: Vec<(u64, String, String)> = TARGETS
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter_map(|(cgid, target)| match target.finishing.take() {
+            Some(reason) => Some((*cgid, target.state.id.clone(), reason)),
+            None => {
+                target.finishing = target.finish_reason(now);
+                None
+            }
+        })
+        .collect();
+
+    for (cgid, id, reason) in finished {
+        println!("{}: {}, writing the profile", id, reason);
+        match unregister_target(skel, cgid, trace_mode) {
+            Ok(()) => {
+                FINISHED.lock().unwrap().insert(id);
+            }
+            // the target is still registered and found to be finished again
+            Err(e) => eprintln!("finish {}: {:#}", id, e),
+        }
+    }
+}
 
-    systrace_skel.rodata().target_cgid = get_contianer_cgroup_id(&state.id)?;
+/// Rewrite the profiles of the targets whose checkpoint interval has elapsed
+pub fn checkpoint_targets(skel: &mut SystraceSkel, trace_mode: TraceMode) {
+    let now = Instant::now();
+    let mut targets = TARGETS.lock().unwrap();
 
-    let mut skel = systrace_skel.load()?;
+    for (cgid, target) in targets.iter_mut() {
+        if !target.is_checkpoint_due(now) {
+            continue;
+        }
+        target.last_checkpoint = now;
 
-    skel.attach()?;
+        // tracing goes on, the next checkpoint or the final write may succeed
+        if let Err(e) = target
+            .collect(skel, *cgid, trace_mode)
+            .and_then(|_| target.flush_event_log())
+            .and_then(|_| target.write_profile(trace_mode, true))
+        {
+            eprintln!("checkpoint {}: {:#}", target.state.id, e);
+        }
+    }
+}
 
+/// Find the cgroup id of a registered container
+pub fn find_target(container_id: &str) -> Option<u64> {
+    TARGETS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(_, target)| target.state.id == container_id)
+        .map(|(cgid, _)| *cgid)
+}
+
+pub fn target_cgroups() -> Vec<u64> {
+    TARGETS.lock().unwrap().keys().copied().collect()
+}
+
+/// Poll perf events until `spinlock` is cleared, `on_tick` is called between polls
+///
+/// The events queued when `spinlock` is cleared are polled before returning.
+pub fn poll_events<F>(skel: &mut SystraceSkel, spinlock: &AtomicBool, mut on_tick: F) -> Result<()>
+where
+    F: FnMut(&mut SystraceSkel) -> Result<()>,
+{
     let perf = PerfBufferBuilder::new(skel.maps().sys_enter_events())
         .sample_cb(handle_event)
         .lost_cb(handle_lost_event)
@@ -110,51 +902,230 @@ fn start_tracing(spinlock: Arc<AtomicBool>, state: &State) -> Result<()> {
             Ok(()) | Err(Error::System(4)) => {} // EINTER
             Err(e) => return Err(e.into()),
         };
-    }
 
-    if let Some(path) = annotation::get_trace_target_path(state) {
-        let file = File::create(path)?;
-        serde_json::to_writer(file, &gen_seccomp_rule()?)?;
+        collect_all_call_sites(skel);
+        on_tick(skel)?;
     }
 
-    Ok(())
+    // e.g. the exit_group(2) of a command which was reaped by `on_tick`
+    match perf.poll(Duration::from_millis(0)) {
+        Ok(()) | Err(Error::System(4)) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
 }
 
-pub fn trace_command() -> Result<()> {
-    let state = process::container_state_load_from_reader(io::stdin()).expect("state load error:");
-
-    let pid = std::process::id() as i32;
-    println!("PID: {pid}");
-
-    process::create_pid_file(state.bundle.join("sprofiler.pid"), pid)?;
-
+/// Stop on the termination signals, SIGUSR2 marks the end of the startup phase
+///
+/// Returns the flags for `poll_events` and `update_phases`.
+pub fn spawn_signal_handler() -> Result<(Arc<AtomicBool>, Arc<AtomicBool>)> {
     let spinlock = Arc::new(AtomicBool::new(true));
     let spinlock_clone = Arc::clone(&spinlock);
+    let steady_marker = Arc::new(AtomicBool::new(false));
+    let steady_marker_clone = Arc::clone(&steady_marker);
 
-    let th = thread::spawn(move || {
-        let mut sigs = vec![SIGUSR1, SIGUSR2, SIGTERM];
-        sigs.extend(TERM_SIGNALS);
-        let mut signals = SignalsInfo::<WithOrigin>::new(&sigs).expect("Signal new");
-        for info in &mut signals {
-            if info.signal == SIGUSR1 {
-                spinlock_clone.store(false, Ordering::SeqCst);
-                break;
+    let mut sigs = vec![SIGUSR2];
+    sigs.extend(TERM_SIGNALS);
+    let mut signals = Signals::new(&sigs)?;
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if signal == SIGUSR2 {
+                steady_marker_clone.store(true, Ordering::SeqCst);
+                continue;
             }
+            spinlock_clone.store(false, Ordering::SeqCst);
+            break;
         }
     });
 
-    start_tracing(spinlock, &state).context("start_tracing: ")?;
+    Ok((spinlock, steady_marker))
+}
+
+/// Answer the control requests about a registered container
+pub fn handle_target_request(
+    skel: &mut SystraceSkel,
+    request: Request,
+    trace_mode: TraceMode,
+) -> Result<Response> {
+    let id = match &request {
+        Request::Register { state } => bail!("{} is already traced", state.id),
+        Request::Unregister { id }
+        | Request::Status { id }
+        | Request::Flush { id }
+        | Request::Syscalls { id } => id.clone(),
+    };
+    // the profile was written when the cgroup of the container became empty
+    if let Request::Unregister { .. } = request {
+        if FINISHED.lock().unwrap().remove(&id) {
+            return Ok(Response::Ok);
+        }
+    }
+    let cgid = find_target(&id).with_context(|| format!("container {} is not traced", id))?;
+
+    if let Request::Unregister { .. } = request {
+        unregister_target(skel, cgid, trace_mode)?;
+        return Ok(Response::Ok);
+    }
+
+    let mut targets = TARGETS.lock().unwrap();
+    let target = targets
+        .get_mut(&cgid)
+        .with_context(|| format!("container {} is not traced", id))?;
+    target.collect(skel, cgid, trace_mode)?;
+
+    let response = match request {
+        Request::Flush { .. } => {
+            target.flush_event_log()?;
+            target.write_profile(trace_mode, true)?;
+            Response::Ok
+        }
+        Request::Syscalls { .. } => Response::Syscalls {
+            names: target
+                .all_syscalls()
+                .syscall_names()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        },
+        _ => Response::Tracing {
+            id,
+            cgroup_id: cgid,
+            steady_state: target.steady.is_some(),
+            syscalls: target.all_syscalls().syscall_names().len(),
+        },
+    };
+
+    Ok(response)
+}
 
-    th.join().expect("thread join: ");
+fn start_tracing(
+    state: &State,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+    output: &OutputOptions,
+) -> Result<()> {
+    let socket = control::tracer_socket_path(&state.id);
+    let listener = control::bind_socket(&socket)?;
+
+    let result = trace_target(state, listener, checkpoint_interval, max_duration, output);
+
+    // stop_tracing takes a leftover socket for a running tracer
+    if let Err(e) = fs::remove_file(&socket) {
+        eprintln!("failed to remove {}: {}", socket.display(), e);
+    }
+
+    result
+}
+
+/// Trace the container until its profile is written, control requests come from `listener`
+fn trace_target(
+    state: &State,
+    listener: UnixListener,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+    output: &OutputOptions,
+) -> Result<()> {
+    let trace_mode = annotation::get_trace_mode(state)?;
+
+    let skel_builder = SystraceSkelBuilder::default();
+    let mut systrace_skel = skel_builder.open()?;
+    configure_skel(
+        &mut systrace_skel,
+        trace_mode,
+        annotation::get_capabilities_path(state).is_some(),
+    )?;
+
+    let mut skel = systrace_skel
+        .load()
+        .context("failed to load the BPF program")?;
+
+    skel.attach().context("failed to attach the BPF program")?;
+
+    register_target(
+        &mut skel,
+        state,
+        trace_mode,
+        checkpoint_interval,
+        max_duration,
+        output,
+    )?;
+
+    let requests = control::spawn_listener(listener);
+    let (spinlock, steady_marker) = spawn_signal_handler()?;
+
+    poll_events(&mut skel, &spinlock, |skel| {
+        control::handle_requests(&requests, |request| {
+            handle_target_request(skel, request, trace_mode)
+        });
+        finish_targets(skel, trace_mode);
+        // the profile was written by an unregister request or the container is gone
+        if target_cgroups().is_empty() {
+            spinlock.store(false, Ordering::SeqCst);
+        }
+
+        update_phases(
+            skel,
+            trace_mode,
+            steady_marker.swap(false, Ordering::SeqCst),
+        );
+        checkpoint_targets(skel, trace_mode);
+        Ok(())
+    })?;
+
+    // the other profiles are written even if one of them fails
+    for cgid in target_cgroups() {
+        if let Err(e) = unregister_target(&mut skel, cgid, trace_mode) {
+            eprintln!("cgroup {}: {:#}", cgid, e);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn trace_command(
+    config: &Config,
+    state: &State,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+) -> Result<()> {
+    let pid = std::process::id() as i32;
+    println!("PID: {pid}");
+
+    let output = config.output_options()?;
+    start_tracing(state, checkpoint_interval, max_duration, &output).context("start_tracing")?;
+
+    Ok(())
+}
+
+/// Ask the tracer of the container to write the profile and wait until it is written
+pub fn stop_tracing(state: &State) -> anyhow::Result<()> {
+    let socket = control::tracer_socket_path(&state.id);
+    // the tracer exits by itself once the cgroup of the container is gone
+    if !socket.exists() {
+        println!("{}: tracer already exited", state.id);
+        return Ok(());
+    }
+    control::send_request(
+        &socket,
+        &Request::Unregister {
+            id: state.id.clone(),
+        },
+    )?;
 
     Ok(())
 }
 
-pub fn stop_tracing() -> anyhow::Result<()> {
-    let state =
-        process::container_state_load_from_reader(std::io::stdin()).expect("state load error:");
-    let pid = process::read_pid_file(state.bundle.join("sprofiler.pid"))?;
-    kill(Pid::from_raw(pid), Signal::SIGUSR1)?;
+/// Send a control request about a container to its tracer or to the daemon
+pub fn control_command(
+    daemon: Option<&Path>,
+    command: ControlCommand,
+    container_id: String,
+) -> Result<()> {
+    let socket = match daemon {
+        Some(socket) => socket.to_path_buf(),
+        None => control::tracer_socket_path(&container_id),
+    };
+    let response = control::send_request(&socket, &command.request(container_id))?;
+    println!("{}", serde_json::to_string_pretty(&response)?);
 
     Ok(())
 }
@@ -1,13 +1,103 @@
 use std::path::PathBuf;
+use std::str::FromStr;
 
+use anyhow::{Context, Result};
 use oci_runtime_spec::State;
 
-const SPROFILER_OCI_ANNOTATION: &str = "io.sprofiler.output_seccomp_profile_path";
+use crate::dynamic::options::{FailedSyscalls, StartTrigger, TraceMode};
+
+pub const SPROFILER_OCI_ANNOTATION: &str = "io.sprofiler.output_seccomp_profile_path";
+pub const SPROFILER_TRACE_MODE_ANNOTATION: &str = "io.sprofiler.trace_mode";
+pub const SPROFILER_START_TRIGGER_ANNOTATION: &str = "io.sprofiler.start_trigger";
+pub const SPROFILER_CAPABILITIES_ANNOTATION: &str = "io.sprofiler.output_capabilities_path";
+const SPROFILER_FAILED_SYSCALLS_ANNOTATION: &str = "io.sprofiler.failed_syscalls";
+const SPROFILER_CHECKPOINT_INTERVAL_ANNOTATION: &str = "io.sprofiler.checkpoint_interval";
+const SPROFILER_STARTUP_PERIOD_ANNOTATION: &str = "io.sprofiler.startup_period";
+const SPROFILER_MAX_DURATION_ANNOTATION: &str = "io.sprofiler.max_duration";
+const SPROFILER_PROCESS_REPORT_ANNOTATION: &str = "io.sprofiler.process_report";
+const SPROFILER_EVENT_LOG_ANNOTATION: &str = "io.sprofiler.event_log";
+const SPROFILER_CALL_SITES_ANNOTATION: &str = "io.sprofiler.call_sites";
+
+fn get_annotation<'a>(state: &'a State, key: &str) -> Option<&'a str> {
+    state
+        .annotations
+        .as_ref()
+        .and_then(|annotations| annotations.get(key))
+        .map(String::as_str)
+}
 
 pub fn get_trace_target_path(state: &State) -> Option<PathBuf> {
-    if let Some(annotations) = &state.annotations {
-        annotations.get(SPROFILER_OCI_ANNOTATION).map(PathBuf::from)
-    } else {
-        None
+    get_annotation(state, SPROFILER_OCI_ANNOTATION).map(PathBuf::from)
+}
+
+/// Where to write the capabilities used by the container
+pub fn get_capabilities_path(state: &State) -> Option<PathBuf> {
+    get_annotation(state, SPROFILER_CAPABILITIES_ANNOTATION).map(PathBuf::from)
+}
+
+pub fn get_trace_mode(state: &State) -> Result<TraceMode> {
+    match get_annotation(state, SPROFILER_TRACE_MODE_ANNOTATION) {
+        Some(mode) => TraceMode::from_str(mode)
+            .with_context(|| format!("invalid {}", SPROFILER_TRACE_MODE_ANNOTATION)),
+        None => Ok(TraceMode::default()),
+    }
+}
+
+pub fn get_start_trigger(state: &State) -> Result<StartTrigger> {
+    match get_annotation(state, SPROFILER_START_TRIGGER_ANNOTATION) {
+        Some(trigger) => StartTrigger::from_str(trigger)
+            .with_context(|| format!("invalid {}", SPROFILER_START_TRIGGER_ANNOTATION)),
+        None => Ok(StartTrigger::default()),
+    }
+}
+
+pub fn get_failed_syscalls(state: &State) -> Result<FailedSyscalls> {
+    match get_annotation(state, SPROFILER_FAILED_SYSCALLS_ANNOTATION) {
+        Some(policy) => FailedSyscalls::from_str(policy)
+            .with_context(|| format!("invalid {}", SPROFILER_FAILED_SYSCALLS_ANNOTATION)),
+        None => Ok(FailedSyscalls::default()),
+    }
+}
+
+fn get_u64(state: &State, key: &str) -> Result<Option<u64>> {
+    get_annotation(state, key)
+        .map(|value| value.parse().with_context(|| format!("invalid {}", key)))
+        .transpose()
+}
+
+/// Checkpoint interval in seconds, 0 disables checkpoints
+pub fn get_checkpoint_interval(state: &State) -> Result<Option<u64>> {
+    get_u64(state, SPROFILER_CHECKPOINT_INTERVAL_ANNOTATION)
+}
+
+/// Length of the startup phase in seconds
+pub fn get_startup_period(state: &State) -> Result<Option<u64>> {
+    get_u64(state, SPROFILER_STARTUP_PERIOD_ANNOTATION)
+}
+
+/// Longest tracing in seconds, the profile is written even if the container is still running
+pub fn get_max_duration(state: &State) -> Result<Option<u64>> {
+    get_u64(state, SPROFILER_MAX_DURATION_ANNOTATION)
+}
+
+fn get_bool_annotation(state: &State, key: &str) -> Result<bool> {
+    match get_annotation(state, key) {
+        Some(enabled) => enabled.parse().with_context(|| format!("invalid {}", key)),
+        None => Ok(false),
     }
 }
+
+/// Whether to write the syscalls of each process
+pub fn get_process_report(state: &State) -> Result<bool> {
+    get_bool_annotation(state, SPROFILER_PROCESS_REPORT_ANNOTATION)
+}
+
+/// Whether to write every observed event to the event log
+pub fn get_event_log(state: &State) -> Result<bool> {
+    get_bool_annotation(state, SPROFILER_EVENT_LOG_ANNOTATION)
+}
+
+/// Whether to capture the user stack of the first call of each syscall
+pub fn get_call_sites(state: &State) -> Result<bool> {
+    get_bool_annotation(state, SPROFILER_CALL_SITES_ANNOTATION)
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, HashSet};
+
+use oci_runtime_spec::{LinuxSeccompAction, LinuxSeccompArg, LinuxSyscall};
+use sprofiler_sys::arch::x86_64;
+
+/// Number of arguments passed by raw_syscalls/sys_enter
+pub const MAX_SYSCALL_ARGS: usize = 6;
+
+/// Size of the `arg_masks` table in systrace.bpf.c
+pub const MAX_SYSCALL_NR: usize = 512;
+
+/// Scalar syscall argument which is recorded by the tracer and
+/// emitted as a seccomp argument condition.
+pub struct ArgFilter {
+    pub syscall: &'static str,
+    pub index: usize,
+    /// Compare only the masked bits (SCMP_CMP_MASKED_EQ)
+    pub mask: Option<u64>,
+}
+
+// socket(2): strip SOCK_NONBLOCK and SOCK_CLOEXEC from type
+const SOCK_TYPE_MASK: u64 = 0xf;
+// ioctl(2): request is an unsigned int
+const IOCTL_REQUEST_MASK: u64 = 0xffff_ffff;
+
+#[rustfmt::skip]
+pub const ARG_FILTERS: &[ArgFilter] = &[
+    ArgFilter { syscall: "clone",       index: 0, mask: None },
+    ArgFilter { syscall: "socket",      index: 0, mask: None },
+    ArgFilter { syscall: "socket",      index: 1, mask: Some(SOCK_TYPE_MASK) },
+    ArgFilter { syscall: "ioctl",       index: 1, mask: Some(IOCTL_REQUEST_MASK) },
+    ArgFilter { syscall: "prctl",       index: 0, mask: None },
+    ArgFilter { syscall: "personality", index: 0, mask: None },
+];
+
+/// Observed argument values of argument-filtered syscalls, in `ARG_FILTERS` order
+pub type ObservedArgs = HashMap<&'static str, HashSet<Vec<u64>>>;
+
+fn filters_for(syscall: &str) -> impl Iterator<Item = &'static ArgFilter> + '_ {
+    ARG_FILTERS.iter().filter(move |f| f.syscall == syscall)
+}
+
+pub fn is_arg_filtered(syscall: &str) -> bool {
+    filters_for(syscall).next().is_some()
+}
+
+/// Build the per syscall number bitmask of argument indexes the BPF program should record
+pub fn arg_masks() -> [u8; MAX_SYSCALL_NR] {
+    let mut masks = [0u8; MAX_SYSCALL_NR];
+    for filter in ARG_FILTERS {
+        for nr in x86_64::to_syscall_number(filter.syscall) {
+            if let Some(mask) = masks.get_mut(nr as usize) {
+                *mask |= 1 << filter.index;
+            }
+        }
+    }
+    masks
+}
+
+/// Pick the filtered argument values out of raw syscall arguments
+pub fn filtered_values(syscall: &str, args: &[u64; MAX_SYSCALL_ARGS]) -> Vec<u64> {
+    filters_for(syscall)
+        .map(|f| args[f.index] & f.mask.unwrap_or(u64::MAX))
+        .collect()
+}
+
+fn seccomp_args(syscall: &str, values: &[u64]) -> Vec<LinuxSeccompArg> {
+    filters_for(syscall)
+        .zip(values)
+        .map(|(f, value)| match f.mask {
+            Some(mask) => LinuxSeccompArg {
+                index: f.index as u64,
+                value: mask,
+                value_two: Some(*value),
+                op: "SCMP_CMP_MASKED_EQ".to_string(),
+            },
+            None => LinuxSeccompArg {
+                index: f.index as u64,
+                value: *value,
+                value_two: None,
+                op: "SCMP_CMP_EQ".to_string(),
+            },
+        })
+        .collect()
+}
+
+/// Generate one allow rule per observed combination of argument values
+pub fn gen_arg_rules(observed: &ObservedArgs) -> Vec<LinuxSyscall> {
+    let mut syscalls: Vec<&&str> = observed.keys().collect();
+    syscalls.sort();
+
+    let mut rules = Vec::new();
+    for syscall in syscalls {
+        let mut values: Vec<&Vec<u64>> = observed[syscall].iter().collect();
+        values.sort();
+
+        for values in values {
+            rules.push(LinuxSyscall {
+                names: vec![syscall.to_string()],
+                action: LinuxSeccompAction::SCMP_ACT_ALLOW,
+                args: Some(seccomp_args(syscall, values)),
+                errno_ret: None,
+            });
+        }
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AF_INET: u64 = 2;
+    const AF_NETLINK: u64 = 16;
+    const SOCK_STREAM: u64 = 1;
+    const SOCK_CLOEXEC: u64 = 0o2000000;
+
+    #[test]
+    fn arg_masks_socket() {
+        let masks = arg_masks();
+        // socket(2) is 41 on x86_64
+        assert_eq!(masks[41], 0b11);
+        // ioctl(2) is 16 on x86_64
+        assert_eq!(masks[16], 0b10);
+        // read(2) has no filter
+        assert_eq!(masks[0], 0);
+    }
+
+    #[test]
+    fn filtered_values_strip_socket_flags() {
+        let args = [AF_INET, SOCK_STREAM | SOCK_CLOEXEC, 0, 0, 0, 0];
+        assert_eq!(filtered_values("socket", &args), vec![AF_INET, SOCK_STREAM]);
+        assert!(filtered_values("read", &args).is_empty());
+    }
+
+    #[test]
+    fn gen_arg_rules_socket_domains() {
+        let mut observed = ObservedArgs::new();
+        observed
+            .entry("socket")
+            .or_default()
+            .insert(vec![AF_INET, SOCK_STREAM]);
+
+        let rules = gen_arg_rules(&observed);
+
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].names, vec!["socket".to_string()]);
+        let args = rules[0].args.as_ref().unwrap();
+        assert_eq!(args[0].index, 0);
+        assert_eq!(args[0].value, AF_INET);
+        assert_ne!(args[0].value, AF_NETLINK);
+        assert_eq!(args[0].op, "SCMP_CMP_EQ");
+        assert_eq!(args[1].index, 1);
+        assert_eq!(args[1].value, SOCK_TYPE_MASK);
+        assert_eq!(args[1].value_two, Some(SOCK_STREAM));
+        assert_eq!(args[1].op, "SCMP_CMP_MASKED_EQ");
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use sprofiler_sys::symbolize::{self, Symbolizer};
+
+/// User-space stack of the first call of a syscall
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CallSite {
+    pub syscall: &'static str,
+    pub pid: u32,
+    /// The innermost frame first
+    pub frames: Vec<String>,
+}
+
+/// Call sites of the syscalls of a container, symbolized while the caller is alive
+#[derive(Debug, Default)]
+pub struct CallSites {
+    symbolizer: Symbolizer,
+    sites: BTreeMap<&'static str, CallSite>,
+}
+
+impl CallSites {
+    pub fn contains(&self, syscall: &str) -> bool {
+        self.sites.contains_key(syscall)
+    }
+
+    /// Symbolize `ips` of `pid`, only the first call site of a syscall is kept
+    pub fn record(&mut self, syscall: &'static str, pid: u32, ips: &[u64]) {
+        if self.contains(syscall) {
+            return;
+        }
+
+        // the process may be gone, its frames are left as addresses
+        let mappings = symbolize::read_maps(pid).unwrap_or_default();
+        let frames = ips
+            .iter()
+            .enumerate()
+            .map(|(i, ip)| {
+                // return addresses point after the call instruction
+                let lookup_ip = if i == 0 { *ip } else { ip.saturating_sub(1) };
+                let mut frame = self.symbolizer.symbolize(pid, &mappings, lookup_ip);
+                frame.ip = *ip;
+                frame.to_string()
+            })
+            .collect();
+
+        self.sites.insert(
+            syscall,
+            CallSite {
+                syscall,
+                pid,
+                frames,
+            },
+        );
+    }
+
+    /// Call sites sorted by syscall name
+    pub fn report(&self) -> Vec<CallSite> {
+        self.sites.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_first_call_site() {
+        let mut call_sites = CallSites::default();
+        call_sites.record("write", u32::MAX, &[0x1000, 0x2000]);
+        call_sites.record("write", u32::MAX, &[0x3000]);
+        call_sites.record("read", u32::MAX, &[0x4000]);
+
+        let report = call_sites.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].syscall, "read");
+        assert_eq!(report[1].frames, vec!["0x1000", "0x2000"]);
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+
+use oci_runtime_spec::LinuxCapabilities;
+
+/// Capability names indexed by number (include/uapi/linux/capability.h)
+#[rustfmt::skip]
+const CAPABILITIES: [&str; 41] = [
+    "CAP_CHOWN", "CAP_DAC_OVERRIDE", "CAP_DAC_READ_SEARCH", "CAP_FOWNER",
+    "CAP_FSETID", "CAP_KILL", "CAP_SETGID", "CAP_SETUID",
+    "CAP_SETPCAP", "CAP_LINUX_IMMUTABLE", "CAP_NET_BIND_SERVICE", "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN", "CAP_NET_RAW", "CAP_IPC_LOCK", "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE", "CAP_SYS_RAWIO", "CAP_SYS_CHROOT", "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT", "CAP_SYS_ADMIN", "CAP_SYS_BOOT", "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE", "CAP_SYS_TIME", "CAP_SYS_TTY_CONFIG", "CAP_MKNOD",
+    "CAP_LEASE", "CAP_AUDIT_WRITE", "CAP_AUDIT_CONTROL", "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE", "CAP_MAC_ADMIN", "CAP_SYSLOG", "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND", "CAP_AUDIT_READ", "CAP_PERFMON", "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+pub fn capability_name(cap: u32) -> Option<&'static str> {
+    CAPABILITIES.get(cap as usize).copied()
+}
+
+/// Capabilities checked by cap_capable(9) in a single container
+#[derive(Debug, Default, Clone)]
+pub struct CapabilityRecorder {
+    granted: BTreeSet<&'static str>,
+    denied: BTreeSet<&'static str>,
+}
+
+impl CapabilityRecorder {
+    pub fn record(&mut self, cap: u32, granted: u64, denied: u64) {
+        let name = match capability_name(cap) {
+            Some(name) => name,
+            None => return,
+        };
+
+        if granted > 0 {
+            self.granted.insert(name);
+        }
+        if denied > 0 {
+            self.denied.insert(name);
+        }
+    }
+
+    /// Checked capabilities which the container did not have
+    pub fn denied(&self) -> Vec<&'static str> {
+        self.denied.difference(&self.granted).copied().collect()
+    }
+
+    /// The capabilities which were used, nothing is inherited
+    pub fn linux_capabilities(&self) -> LinuxCapabilities {
+        let used: Vec<String> = self.granted.iter().map(|cap| cap.to_string()).collect();
+
+        LinuxCapabilities {
+            bounding: Some(used.clone()),
+            effective: Some(used.clone()),
+            inheritable: Some(vec![]),
+            permitted: Some(used),
+            ambient: Some(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimal_capabilities() {
+        let mut recorder = CapabilityRecorder::default();
+        recorder.record(10, 3, 0);
+        recorder.record(0, 1, 0);
+        recorder.record(21, 0, 2);
+        recorder.record(12, 1, 1);
+        recorder.record(64, 1, 0);
+
+        let capabilities = recorder.linux_capabilities();
+        let used = vec!["CAP_CHOWN", "CAP_NET_ADMIN", "CAP_NET_BIND_SERVICE"];
+        assert_eq!(capabilities.bounding.unwrap(), used);
+        assert_eq!(capabilities.effective.unwrap(), used);
+        assert_eq!(capabilities.permitted.unwrap(), used);
+        assert!(capabilities.ambient.unwrap().is_empty());
+        assert_eq!(recorder.denied(), vec!["CAP_SYS_ADMIN"]);
+    }
+}
@@ -0,0 +1,394 @@
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use oci_runtime_spec::State;
+use serde::Deserialize;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// Parent of the cgroups created by `sprofiler dynamic run`
+const TRANSIENT_CGROUP_PARENT: &str = "sprofiler";
+
+/// cgroup v2 directory where the container processes are placed
+#[derive(Clone, Debug)]
+pub struct ContainerCgroup {
+    pub path: PathBuf,
+    /// cgroup id seen by bpf_get_current_cgroup_id (inode number of the directory)
+    pub id: u64,
+}
+
+impl ContainerCgroup {
+    pub fn from_path(path: PathBuf) -> Result<Self> {
+        let meta = fs::metadata(&path)
+            .with_context(|| format!("failed to get metadata from {}", path.display()))?;
+        Ok(ContainerCgroup {
+            path,
+            id: meta.ino(),
+        })
+    }
+
+    /// Create an empty cgroup under /sys/fs/cgroup/sprofiler
+    pub fn create_transient(name: &str) -> Result<Self> {
+        let path = Path::new(CGROUP_ROOT)
+            .join(TRANSIENT_CGROUP_PARENT)
+            .join(name);
+        fs::create_dir_all(&path)
+            .with_context(|| format!("failed to create cgroup {}", path.display()))?;
+        Self::from_path(path)
+    }
+
+    /// cgroup of a running process
+    pub fn from_pid(pid: i32) -> Result<Self> {
+        Self::from_path(cgroup_from_pid(pid)?)
+    }
+
+    /// Search the cgroup tree for the cgroup of a running container, `id` may be
+    /// a prefix of the full container id
+    pub fn find_container(id: &str) -> Result<Self> {
+        let mut found = Vec::new();
+        find_container_cgroups(Path::new(CGROUP_ROOT), id, &mut found)?;
+
+        let path = match found.as_slice() {
+            [path] => path,
+            [] => bail!("no cgroup found for container {}", id),
+            _ => bail!("container id {} is ambiguous: {:?}", id, found),
+        };
+
+        // crun and podman move the container processes into a "container" sub-cgroup
+        let container = path.join("container");
+        if container.is_dir() {
+            Self::from_path(container)
+        } else {
+            Self::from_path(path.to_path_buf())
+        }
+    }
+
+    /// Path of the file to which a pid is written to move the process into the cgroup
+    pub fn procs_path(&self) -> PathBuf {
+        self.path.join("cgroup.procs")
+    }
+
+    /// Whether processes are left in the cgroup or its descendants, false once it is removed
+    pub fn is_populated(&self) -> Result<bool> {
+        let events_path = self.path.join("cgroup.events");
+        let events = match fs::read_to_string(&events_path) {
+            Ok(events) => events,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", events_path.display()))
+            }
+        };
+        parse_populated(&events)
+            .with_context(|| format!("no populated field in {}", events_path.display()))
+    }
+
+    /// Remove the cgroup, it must not contain processes
+    pub fn remove(&self) -> Result<()> {
+        fs::remove_dir(&self.path)
+            .with_context(|| format!("failed to remove cgroup {}", self.path.display()))
+    }
+}
+
+// Only the fields needed here; oci_runtime_spec::Spec is too strict for
+// config.json files written by every runtime.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct BundleLinux {
+    #[serde(default)]
+    cgroups_path: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct BundleConfig {
+    #[serde(default)]
+    linux: Option<BundleLinux>,
+}
+
+/// Get the unified hierarchy path ("0::/path") from the content of /proc/<pid>/cgroup
+fn parse_proc_cgroup(content: &str) -> Option<PathBuf> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(|path| PathBuf::from(path.trim_start_matches('/')))
+}
+
+/// Get the `populated` field from the content of cgroup.events
+fn parse_populated(events: &str) -> Option<bool> {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("populated "))
+        .map(|populated| populated.trim() == "1")
+}
+
+/// Expand a systemd slice name to its path, e.g. "a-b.slice" -> "a.slice/a-b.slice"
+fn expand_slice(slice: &str) -> Option<PathBuf> {
+    if slice == "-.slice" {
+        return Some(PathBuf::new());
+    }
+
+    let name = slice.strip_suffix(".slice")?;
+    let mut path = PathBuf::new();
+    let mut prefix = String::new();
+    for part in name.split('-') {
+        if part.is_empty() {
+            return None;
+        }
+        if !prefix.is_empty() {
+            prefix.push('-');
+        }
+        prefix.push_str(part);
+        path.push(format!("{}.slice", prefix));
+    }
+    Some(path)
+}
+
+/// Convert `linux.cgroupsPath` to a path relative to the cgroup root
+///
+/// The systemd cgroup driver uses "slice:prefix:name", the cgroupfs driver uses a path.
+fn parse_cgroups_path(cgroups_path: &str) -> Option<PathBuf> {
+    if cgroups_path.starts_with('/') {
+        return Some(PathBuf::from(cgroups_path.trim_start_matches('/')));
+    }
+
+    let parts: Vec<&str> = cgroups_path.split(':').collect();
+    match parts.as_slice() {
+        [slice, prefix, name] => {
+            let slice = if slice.is_empty() {
+                "system.slice"
+            } else {
+                slice
+            };
+            let unit = if name.ends_with(".slice") {
+                name.to_string()
+            } else if prefix.is_empty() {
+                format!("{}.scope", name)
+            } else {
+                format!("{}-{}.scope", prefix, name)
+            };
+            Some(expand_slice(slice)?.join(unit))
+        }
+        [_] => Some(PathBuf::from(cgroups_path)),
+        _ => None,
+    }
+}
+
+/// Whether a cgroup directory name is the cgroup of container `id`
+///
+/// The systemd driver creates a scope, e.g. "libpod-<id>.scope", "docker-<id>.scope"
+/// or "crio-<id>.scope". conmon runs in a sibling scope, "libpod-conmon-<id>.scope",
+/// which is not a container id. The cgroupfs driver names the directory after the
+/// full container id, e.g. "docker/<id>" or "libpod_parent/libpod-<id>".
+fn is_container_cgroup(name: &str, id: &str) -> bool {
+    const RUNTIME_PREFIXES: [&str; 4] = ["libpod-", "docker-", "crio-", "cri-containerd-"];
+    /// Length of a full container id
+    const CONTAINER_ID_LEN: usize = 64;
+
+    let is_container_id = |container_id: &str| {
+        !id.is_empty()
+            && container_id.starts_with(id)
+            && container_id.chars().all(|c| c.is_ascii_hexdigit())
+    };
+
+    let has_container_id = |unit: &str| {
+        RUNTIME_PREFIXES.iter().any(|prefix| {
+            matches!(unit.strip_prefix(prefix), Some(container_id) if is_container_id(container_id))
+        })
+    };
+
+    match name.strip_suffix(".scope") {
+        Some(unit) => has_container_id(unit),
+        // the full id keeps other directories with hex names from matching
+        None => {
+            let container_id = RUNTIME_PREFIXES
+                .iter()
+                .find_map(|prefix| name.strip_prefix(prefix))
+                .unwrap_or(name);
+            container_id.len() == CONTAINER_ID_LEN && is_container_id(container_id)
+        }
+    }
+}
+
+fn find_container_cgroups(dir: &Path, id: &str, found: &mut Vec<PathBuf>) -> Result<()> {
+    // cgroups of exiting containers are removed while the tree is walked
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", dir.display())),
+    };
+
+    for entry in entries {
+        let (entry, file_type) = match entry.and_then(|entry| Ok((entry.file_type()?, entry))) {
+            Ok((file_type, entry)) => (entry, file_type),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", dir.display())),
+        };
+        if !file_type.is_dir() {
+            continue;
+        }
+        let path = entry.path();
+        if is_container_cgroup(&entry.file_name().to_string_lossy(), id) {
+            found.push(path);
+        } else {
+            find_container_cgroups(&path, id, found)?;
+        }
+    }
+    Ok(())
+}
+
+fn cgroup_from_pid(pid: i32) -> Result<PathBuf> {
+    let proc_cgroup = format!("/proc/{}/cgroup", pid);
+    let content = fs::read_to_string(&proc_cgroup)
+        .with_context(|| format!("failed to read {}", proc_cgroup))?;
+    match parse_proc_cgroup(&content) {
+        Some(path) => Ok(Path::new(CGROUP_ROOT).join(path)),
+        None => bail!("no cgroup v2 entry in {}", proc_cgroup),
+    }
+}
+
+fn cgroup_from_bundle(bundle: &Path) -> Result<Vec<PathBuf>> {
+    let config_path = bundle.join("config.json");
+    let file = File::open(&config_path)
+        .with_context(|| format!("failed to open {}", config_path.display()))?;
+    let config: BundleConfig = serde_json::from_reader(file)
+        .with_context(|| format!("failed to parse {}", config_path.display()))?;
+
+    let cgroups_path = match config.linux.and_then(|linux| linux.cgroups_path) {
+        Some(cgroups_path) => cgroups_path,
+        None => bail!("linux.cgroupsPath is not set in {}", config_path.display()),
+    };
+    let path = match parse_cgroups_path(&cgroups_path) {
+        Some(path) => Path::new(CGROUP_ROOT).join(path),
+        None => bail!("invalid linux.cgroupsPath: {}", cgroups_path),
+    };
+
+    // crun and podman move the container processes into a "container" sub-cgroup
+    Ok(vec![path.join("container"), path])
+}
+
+/// Find the cgroup of the container from its init process or from the bundle
+pub fn resolve(state: &State) -> Result<ContainerCgroup> {
+    let mut errors = Vec::new();
+    let mut candidates = Vec::new();
+
+    if let Some(pid) = state.pid {
+        match cgroup_from_pid(pid) {
+            Ok(path) => candidates.push(path),
+            Err(e) => errors.push(format!("{:#}", e)),
+        }
+    }
+
+    match cgroup_from_bundle(&state.bundle) {
+        Ok(paths) => candidates.extend(paths),
+        Err(e) => errors.push(format!("{:#}", e)),
+    }
+
+    for path in candidates {
+        match ContainerCgroup::from_path(path) {
+            Ok(cgroup) => return Ok(cgroup),
+            Err(e) => errors.push(format!("{:#}", e)),
+        }
+    }
+
+    bail!(
+        "cgroup of container {} not found: {}",
+        state.id,
+        errors.join("; ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_proc_cgroup_v2() {
+        let content = "0::/machine.slice/libpod-abc.scope/container\n";
+        assert_eq!(
+            parse_proc_cgroup(content),
+            Some(PathBuf::from("machine.slice/libpod-abc.scope/container"))
+        );
+    }
+
+    #[test]
+    fn parse_proc_cgroup_hybrid() {
+        let content = "12:pids:/docker/abc\n1:name=systemd:/docker/abc\n0::/docker/abc\n";
+        assert_eq!(
+            parse_proc_cgroup(content),
+            Some(PathBuf::from("docker/abc"))
+        );
+        assert_eq!(parse_proc_cgroup("12:pids:/docker/abc\n"), None);
+    }
+
+    #[test]
+    fn parse_cgroup_events() {
+        assert_eq!(parse_populated("populated 1\nfrozen 0\n"), Some(true));
+        assert_eq!(parse_populated("populated 0\nfrozen 0\n"), Some(false));
+        assert_eq!(parse_populated("frozen 0\n"), None);
+    }
+
+    #[test]
+    fn expand_nested_slice() {
+        assert_eq!(
+            expand_slice("user-1000.slice"),
+            Some(PathBuf::from("user.slice/user-1000.slice"))
+        );
+        assert_eq!(
+            expand_slice("machine.slice"),
+            Some(PathBuf::from("machine.slice"))
+        );
+        assert_eq!(expand_slice("-.slice"), Some(PathBuf::new()));
+        assert_eq!(expand_slice("machine"), None);
+    }
+
+    #[test]
+    fn parse_cgroups_path_systemd() {
+        assert_eq!(
+            parse_cgroups_path("machine.slice:libpod:abc"),
+            Some(PathBuf::from("machine.slice/libpod-abc.scope"))
+        );
+        assert_eq!(
+            parse_cgroups_path(":runc:abc"),
+            Some(PathBuf::from("system.slice/runc-abc.scope"))
+        );
+    }
+
+    #[test]
+    fn parse_cgroups_path_cgroupfs() {
+        assert_eq!(
+            parse_cgroups_path("/libpod_parent/libpod-abc"),
+            Some(PathBuf::from("libpod_parent/libpod-abc"))
+        );
+        assert_eq!(parse_cgroups_path("abc"), Some(PathBuf::from("abc")));
+        assert_eq!(parse_cgroups_path("a:b"), None);
+    }
+
+    #[test]
+    fn container_cgroup_names() {
+        assert!(is_container_cgroup("libpod-abcdef.scope", "abc"));
+        assert!(is_container_cgroup("docker-abcdef.scope", "abcdef"));
+        assert!(is_container_cgroup("cri-containerd-0123ab.scope", "01"));
+        assert!(!is_container_cgroup("libpod-conmon-abcdef.scope", "abc"));
+        assert!(!is_container_cgroup("libpod-abcdef.scope", "bcd"));
+        assert!(!is_container_cgroup("libpod-abcdef", "abc"));
+        assert!(!is_container_cgroup("abcdef", "abc"));
+        assert!(!is_container_cgroup("system.slice", ""));
+        assert!(!is_container_cgroup("libpod-abcdef.scope", ""));
+        // systemd units of users and sessions end with numbers too
+        assert!(!is_container_cgroup("user-1000.slice", "1"));
+        assert!(!is_container_cgroup("session-1.scope", "1"));
+        assert!(!is_container_cgroup("run-u1.scope", "1"));
+
+        // cgroupfs driver
+        let id = "0123456789abcdef".repeat(4);
+        assert!(is_container_cgroup(&id, "0123"));
+        assert!(is_container_cgroup(&format!("libpod-{}", id), "0123"));
+        assert!(!is_container_cgroup(&id, "123"));
+        assert!(!is_container_cgroup(&id[1..], "1"));
+        assert!(!is_container_cgroup(
+            &format!("libpod-conmon-{}", id),
+            "0123"
+        ));
+    }
+}
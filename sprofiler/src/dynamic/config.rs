@@ -0,0 +1,118 @@
+use std::env;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::dynamic::output::OutputOptions;
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/sprofiler/sprofiler.json";
+/// Overrides DEFAULT_CONFIG_PATH
+const CONFIG_PATH_ENV: &str = "SPROFILER_CONFIG";
+pub const DEFAULT_OUTPUT_ROOT: &str = "/var/lib/sprofiler";
+
+/// Host-wide settings of the hooks, the runtime does not pass options to them
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Log errors of the hooks and let the container start anyway
+    pub fail_open: bool,
+    /// Append the errors as JSON lines to this file instead of stderr
+    pub error_log: Option<PathBuf>,
+    /// Output paths of the annotations must be below this directory, null allows any path
+    pub output_root: Option<PathBuf>,
+    /// Owner of the written profiles
+    pub output_uid: Option<u32>,
+    pub output_gid: Option<u32>,
+    /// Permissions of the written profiles in octal, e.g. "0640"
+    pub output_mode: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fail_open: true,
+            error_log: None,
+            output_root: Some(PathBuf::from(DEFAULT_OUTPUT_ROOT)),
+            output_uid: None,
+            output_gid: None,
+            output_mode: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn output_options(&self) -> Result<OutputOptions> {
+        let mode = self
+            .output_mode
+            .as_ref()
+            .map(|mode| {
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .with_context(|| format!("invalid outputMode: {}", mode))
+            })
+            .transpose()?;
+
+        Ok(OutputOptions {
+            root: self.output_root.clone(),
+            uid: self.output_uid,
+            gid: self.output_gid,
+            mode,
+        })
+    }
+}
+
+fn config_path() -> PathBuf {
+    env::var_os(CONFIG_PATH_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+}
+
+/// Load the configuration, the defaults are used if there is no configuration file
+pub fn load() -> Result<Config> {
+    let path = config_path();
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("failed to open {}", path.display())),
+    };
+    serde_json::from_reader(file).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_with_defaults() {
+        let config: Config = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, Config::default());
+
+        let config: Config =
+            serde_json::from_str(r#"{"failOpen":false,"errorLog":"/var/log/sprofiler.jsonl"}"#)
+                .unwrap();
+        assert!(!config.fail_open);
+        assert_eq!(
+            config.error_log,
+            Some(PathBuf::from("/var/log/sprofiler.jsonl"))
+        );
+    }
+
+    #[test]
+    fn output_options_from_config() {
+        let config: Config =
+            serde_json::from_str(r#"{"outputRoot":null,"outputUid":1000,"outputMode":"0640"}"#)
+                .unwrap();
+        let options = config.output_options().unwrap();
+        assert_eq!(options.root, None);
+        assert_eq!(options.uid, Some(1000));
+        assert_eq!(options.mode, Some(0o640));
+
+        let options = Config::default().output_options().unwrap();
+        assert_eq!(options.root, Some(PathBuf::from(DEFAULT_OUTPUT_ROOT)));
+
+        let config: Config = serde_json::from_str(r#"{"outputMode":"rw"}"#).unwrap();
+        assert!(config.output_options().is_err());
+    }
+}
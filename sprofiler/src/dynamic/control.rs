@@ -0,0 +1,266 @@
+use std::fs::{self, Permissions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use oci_runtime_spec::State;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_DAEMON_SOCKET: &str = "/run/sprofiler/sprofiler.sock";
+// not in the bundle, its path may exceed the 108 bytes limit of sun_path
+const TRACER_SOCKET_DIR: &str = "/run/sprofiler/tracer";
+
+/// Request sent to the tracer or the tracing daemon, one JSON document per line
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub enum Request {
+    /// Start tracing the container
+    Register { state: State },
+    /// Stop tracing the container and write its profile
+    Unregister { id: String },
+    /// Report how the container is traced
+    Status { id: String },
+    /// Write the profile of the container now, tracing goes on
+    Flush { id: String },
+    /// List the syscalls observed so far
+    Syscalls { id: String },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    Error {
+        message: String,
+    },
+    /// The container is traced
+    #[serde(rename_all = "camelCase")]
+    Tracing {
+        id: String,
+        cgroup_id: u64,
+        steady_state: bool,
+        syscalls: usize,
+    },
+    Syscalls {
+        names: Vec<String>,
+    },
+}
+
+/// Requests which can be sent from the command line
+#[derive(Debug, Clone, Copy)]
+pub enum ControlCommand {
+    Status,
+    Flush,
+    Syscalls,
+}
+
+impl FromStr for ControlCommand {
+    type Err = anyhow::Error;
+
+    #[rustfmt::skip]
+    fn from_str(command: &str) -> Result<ControlCommand, Self::Err> {
+        match command.to_lowercase().as_str() {
+            "status"   => Ok(ControlCommand::Status),
+            "flush"    => Ok(ControlCommand::Flush),
+            "syscalls" => Ok(ControlCommand::Syscalls),
+            _          => bail!("unknown control command: {}", command),
+        }
+    }
+}
+
+impl ControlCommand {
+    pub fn request(self, id: String) -> Request {
+        match self {
+            ControlCommand::Status => Request::Status { id },
+            ControlCommand::Flush => Request::Flush { id },
+            ControlCommand::Syscalls => Request::Syscalls { id },
+        }
+    }
+}
+
+pub type Reply = Sender<Response>;
+
+/// Control socket of the tracer spawned for a single container
+pub fn tracer_socket_path(container_id: &str) -> PathBuf {
+    Path::new(TRACER_SOCKET_DIR).join(format!("{}.sock", container_id))
+}
+
+pub fn read_message<T, R>(reader: &mut R) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+    R: BufRead,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        bail!("connection closed");
+    }
+    let message = serde_json::from_str(&line)?;
+    Ok(message)
+}
+
+pub fn write_message<T, W>(writer: &mut W, message: &T) -> Result<()>
+where
+    T: Serialize,
+    W: Write,
+{
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Send `request` and wait for the response, error responses are returned as Err
+pub fn send_request(socket: &Path, request: &Request) -> Result<Response> {
+    let stream = UnixStream::connect(socket)
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+    exchange(stream, request)
+}
+
+/// Send `request` and wait at most `timeout` for the response
+pub fn send_request_timeout(
+    socket: &Path,
+    request: &Request,
+    timeout: Duration,
+) -> Result<Response> {
+    let stream = UnixStream::connect(socket)
+        .with_context(|| format!("failed to connect to {}", socket.display()))?;
+    stream.set_read_timeout(Some(timeout))?;
+    exchange(stream, request)
+}
+
+fn exchange(mut stream: UnixStream, request: &Request) -> Result<Response> {
+    write_message(&mut stream, request)?;
+
+    let mut reader = BufReader::new(stream);
+    match read_message(&mut reader)? {
+        Response::Error { message } => bail!("sprofiler: {}", message),
+        response => Ok(response),
+    }
+}
+
+pub fn bind_socket(socket: &Path) -> Result<UnixListener> {
+    if let Some(dir) = socket.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if socket.exists() {
+        fs::remove_file(socket)?;
+    }
+
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("failed to bind {}", socket.display()))?;
+    fs::set_permissions(socket, Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+fn handle_connection(stream: UnixStream, requests: Sender<(Request, Reply)>) -> Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_message(&mut reader)?;
+
+    let (reply_tx, reply_rx) = mpsc::channel();
+    requests
+        .send((request, reply_tx))
+        .map_err(|_| anyhow!("tracer is stopped"))?;
+    let response = reply_rx.recv()?;
+
+    write_message(&mut &stream, &response)
+}
+
+/// Accept connections in background threads, requests are handed to the
+/// tracing loop which owns the BPF skeleton
+pub fn spawn_listener(listener: UnixListener) -> Receiver<(Request, Reply)> {
+    let (requests_tx, requests_rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let requests = requests_tx.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, requests) {
+                            eprintln!("control connection: {:#}", e);
+                        }
+                    });
+                }
+                Err(e) => eprintln!("control socket: {}", e),
+            }
+        }
+    });
+
+    requests_rx
+}
+
+/// Answer the pending requests without blocking
+pub fn handle_requests<F>(requests: &Receiver<(Request, Reply)>, mut handler: F)
+where
+    F: FnMut(Request) -> Result<Response>,
+{
+    while let Ok((request, reply)) = requests.try_recv() {
+        let response = handler(request).unwrap_or_else(|e| Response::Error {
+            message: format!("{:#}", e),
+        });
+        // the client may have timed out and closed the connection
+        let _ = reply.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_round_trip() {
+        let mut buf = Vec::new();
+        let request = Request::Unregister {
+            id: "abcdef".to_string(),
+        };
+        write_message(&mut buf, &request).unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "{\"request\":\"unregister\",\"id\":\"abcdef\"}\n"
+        );
+
+        let request: Request = read_message(&mut buf.as_slice()).unwrap();
+        assert!(matches!(request, Request::Unregister { id } if id == "abcdef"));
+    }
+
+    #[test]
+    fn tracing_response_round_trip() {
+        let mut buf = Vec::new();
+        let response = Response::Tracing {
+            id: "abcdef".to_string(),
+            cgroup_id: 1234,
+            steady_state: false,
+            syscalls: 42,
+        };
+        write_message(&mut buf, &response).unwrap();
+        assert_eq!(
+            String::from_utf8(buf.clone()).unwrap(),
+            "{\"status\":\"tracing\",\"id\":\"abcdef\",\"cgroupId\":1234,\"steadyState\":false,\"syscalls\":42}\n"
+        );
+
+        let response: Response = read_message(&mut buf.as_slice()).unwrap();
+        assert!(matches!(response, Response::Tracing { syscalls: 42, .. }));
+    }
+
+    #[test]
+    fn request_times_out_without_answer() {
+        let socket =
+            std::env::temp_dir().join(format!("sprofiler-control-{}.sock", std::process::id()));
+        // connections wait in the backlog, nobody accepts them
+        let _listener = bind_socket(&socket).unwrap();
+
+        let request = Request::Status {
+            id: "abcdef".to_string(),
+        };
+        assert!(send_request_timeout(&socket, &request, Duration::from_millis(100)).is_err());
+
+        fs::remove_file(&socket).unwrap();
+    }
+}
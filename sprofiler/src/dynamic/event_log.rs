@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::dynamic::args::MAX_SYSCALL_ARGS;
+use crate::dynamic::output::{self, OutputOptions};
+
+/// A syscall observed by the tracer, one JSON document per line in the event log
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventRecord {
+    /// Nanoseconds since boot
+    pub ts: u64,
+    pub pid: u32,
+    pub tid: u32,
+    pub uid: u32,
+    pub comm: String,
+    pub cgid: u64,
+    pub nr: i64,
+    /// The syscall was called through the ia32 ABI
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compat: bool,
+    /// Recorded arguments of argument-filtered syscalls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<[u64; MAX_SYSCALL_ARGS]>,
+}
+
+pub struct EventLog {
+    writer: BufWriter<File>,
+}
+
+impl EventLog {
+    pub fn create(path: &Path, options: &OutputOptions) -> Result<Self> {
+        let file = output::create_file(path, options)
+            .with_context(|| format!("failed to create {}", path.display()))?;
+        Ok(EventLog {
+            writer: BufWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, record: &EventRecord) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_events<R: BufRead>(reader: R) -> impl Iterator<Item = Result<EventRecord>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(i, line)| {
+            let record = serde_json::from_str(&line?)
+                .with_context(|| format!("invalid event at line {}", i + 1))?;
+            Ok(record)
+        })
+}
+
+/// Timestamp of the earliest event, perf events of different CPUs are not logged in order
+pub fn earliest_ts<R: BufRead>(reader: R) -> Result<Option<u64>> {
+    let mut earliest = None;
+    for record in read_events(reader) {
+        let ts = record?.ts;
+        earliest = Some(earliest.map_or(ts, |earliest: u64| earliest.min(ts)));
+    }
+    Ok(earliest)
+}
+
+/// Selects the events used to rebuild a profile, empty lists match everything
+#[derive(Debug, Default)]
+pub struct EventFilter {
+    pub comms: Vec<String>,
+    pub uids: Vec<u32>,
+    /// Seconds since the earliest event of the log
+    pub since: Option<f64>,
+    pub until: Option<f64>,
+}
+
+impl EventFilter {
+    /// `start_ts` is the timestamp of the earliest event of the log
+    pub fn matches(&self, record: &EventRecord, start_ts: u64) -> bool {
+        let elapsed = record.ts.saturating_sub(start_ts) as f64 / 1e9;
+
+        (self.comms.is_empty() || self.comms.contains(&record.comm))
+            && (self.uids.is_empty() || self.uids.contains(&record.uid))
+            && !matches!(self.since, Some(since) if elapsed < since)
+            && !matches!(self.until, Some(until) if elapsed >= until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts: u64, comm: &str, uid: u32) -> EventRecord {
+        EventRecord {
+            ts,
+            pid: 1,
+            tid: 1,
+            uid,
+            comm: comm.to_string(),
+            cgid: 1234,
+            nr: 0,
+            compat: false,
+            args: None,
+        }
+    }
+
+    #[test]
+    fn read_written_events() {
+        let log = "{\"ts\":1,\"pid\":1,\"tid\":2,\"uid\":0,\"comm\":\"sh\",\"cgid\":1234,\"nr\":41,\"args\":[2,1,0,0,0,0]}\n\n";
+        let records: Vec<EventRecord> = read_events(log.as_bytes()).collect::<Result<_>>().unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].tid, 2);
+        assert!(!records[0].compat);
+        assert_eq!(records[0].args, Some([2, 1, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn earliest_event_of_unordered_log() {
+        let log = "{\"ts\":20,\"pid\":1,\"tid\":1,\"uid\":0,\"comm\":\"sh\",\"cgid\":1234,\"nr\":0}\n\
+                   {\"ts\":10,\"pid\":1,\"tid\":2,\"uid\":0,\"comm\":\"sh\",\"cgid\":1234,\"nr\":1}\n";
+
+        assert_eq!(earliest_ts(log.as_bytes()).unwrap(), Some(10));
+        assert_eq!(earliest_ts("".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn filter_by_comm_uid_and_time() {
+        let start = 1_000_000_000;
+        let filter = EventFilter {
+            comms: vec!["app".to_string()],
+            uids: vec![1000],
+            since: Some(1.0),
+            until: Some(2.0),
+        };
+
+        assert!(filter.matches(&record(start + 1_500_000_000, "app", 1000), start));
+        assert!(!filter.matches(&record(start + 500_000_000, "app", 1000), start));
+        assert!(!filter.matches(&record(start + 2_000_000_000, "app", 1000), start));
+        assert!(!filter.matches(&record(start + 1_500_000_000, "sh", 1000), start));
+        assert!(!filter.matches(&record(start + 1_500_000_000, "app", 0), start));
+        assert!(EventFilter::default().matches(&record(start, "sh", 0), start));
+    }
+}
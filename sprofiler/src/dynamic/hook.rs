@@ -0,0 +1,146 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use oci_runtime_spec::State;
+use serde::Serialize;
+
+use crate::dynamic::annotation;
+use crate::dynamic::config::{self, Config};
+use crate::dynamic::output;
+use crate::dynamic::process;
+use crate::dynamic::report;
+
+/// Process which failed, the hooks are run by the runtime and the tracer by the prestart hook
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HookStage {
+    Prestart,
+    Poststop,
+    Tracer,
+}
+
+/// Error of a hook, logged as a JSON line and written next to the profile
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HookFailure {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub stage: HookStage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_id: Option<String>,
+    pub error: String,
+    /// Underlying errors, the innermost last
+    pub causes: Vec<String>,
+}
+
+impl HookFailure {
+    pub fn new(stage: HookStage, state: Option<&State>, error: &anyhow::Error) -> Self {
+        HookFailure {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_secs())
+                .unwrap_or_default(),
+            stage,
+            container_id: state.map(|state| state.id.clone()),
+            error: error.to_string(),
+            causes: error
+                .chain()
+                .skip(1)
+                .map(|cause| cause.to_string())
+                .collect(),
+        }
+    }
+}
+
+fn log_failure(config: &Config, failure: &HookFailure) -> Result<()> {
+    let mut line = serde_json::to_vec(failure)?;
+    line.push(b'\n');
+
+    match &config.error_log {
+        Some(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&line))
+            .with_context(|| format!("failed to write {}", path.display())),
+        None => Ok(io::stderr().write_all(&line)?),
+    }
+}
+
+/// Log the failure and record it where the profile would have been written
+fn report_failure(config: &Config, failure: &HookFailure, state: Option<&State>) {
+    if let Err(e) = log_failure(config, failure) {
+        eprintln!("{:#}", e);
+    }
+
+    let path = match state.and_then(annotation::get_trace_target_path) {
+        Some(path) => path,
+        None => return,
+    };
+    // an output path outside of the output root is reported in the log only
+    if let Err(e) = config.output_options().and_then(|options| {
+        let path = options.resolve(&path)?;
+        output::write_json_atomic(&report::error_path(&path), failure, &options)
+    }) {
+        eprintln!("{:#}", e);
+    }
+}
+
+/// Run a hook with the configuration and the container state read from stdin
+///
+/// Failures are reported, and swallowed in fail-open mode so that the runtime
+/// does not abort the container because of sprofiler.
+pub fn run_hook<F>(stage: HookStage, hook: F) -> Result<()>
+where
+    F: FnOnce(&Config, &State) -> Result<()>,
+{
+    let (config, config_error) = match config::load() {
+        Ok(config) => (config, None),
+        Err(e) => (Config::default(), Some(e)),
+    };
+
+    let state = process::container_state_load_from_reader(io::stdin())
+        .context("failed to load the container state");
+
+    let result = match (config_error, state) {
+        (_, Err(e)) => Err((None, e)),
+        (Some(e), Ok(state)) => Err((Some(state), e)),
+        (None, Ok(state)) => hook(&config, &state).map_err(|e| (Some(state), e)),
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err((state, e)) => {
+            report_failure(
+                &config,
+                &HookFailure::new(stage, state.as_ref(), &e),
+                state.as_ref(),
+            );
+            if config.fail_open {
+                Ok(())
+            } else {
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn failure_with_causes() {
+        let error = anyhow!("BTF is not available").context("failed to load BPF program");
+        let failure = HookFailure::new(HookStage::Tracer, None, &error);
+
+        let json = serde_json::to_value(&failure).unwrap();
+        assert_eq!(json["stage"], "tracer");
+        assert_eq!(json["error"], "failed to load BPF program");
+        assert_eq!(json["causes"][0], "BTF is not available");
+        assert!(json.get("containerId").is_none());
+    }
+}
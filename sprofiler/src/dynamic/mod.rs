@@ -1,39 +1,236 @@
 pub mod annotation;
+pub mod args;
+pub mod call_site;
+pub mod capabilities;
+pub mod cgroup;
+pub mod config;
+pub mod control;
+pub mod event_log;
+pub mod hook;
+pub mod options;
+pub mod output;
 pub mod process;
+pub mod process_report;
+pub mod recorder;
+pub mod report;
 
-use std::process::{Command, Stdio};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
 use std::str;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use oci_runtime_spec::State;
 use structopt::StructOpt;
 
-use crate::command::tracer::{stop_tracing, trace_command};
+use crate::command::attach::attach_command;
+use crate::command::daemon::{daemon_command, register_command, unregister_command};
+use crate::command::run::run_command;
+use crate::command::tracer::{control_command, stop_tracing, trace_command};
+use control::{ControlCommand, Request, DEFAULT_DAEMON_SOCKET};
+use hook::{run_hook, HookStage};
+use options::{StartTrigger, TraceMode};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "dynamic", about = "Dynamic Analyzer")]
 pub enum DynamicSubCommand {
-    Start {},
-    Stop {},
-    Tracer {},
+    Start {
+        /// Register the container with a tracing daemon instead of spawning a tracer
+        #[structopt(long, parse(from_os_str))]
+        daemon: Option<PathBuf>,
+        /// Rewrite the profile every N seconds while tracing (0 disables)
+        #[structopt(long)]
+        checkpoint_interval: Option<u64>,
+        /// Write the profile and stop tracing after N seconds (0 disables)
+        #[structopt(long)]
+        max_duration: Option<u64>,
+    },
+    Stop {
+        /// Unregister the container from a tracing daemon
+        #[structopt(long, parse(from_os_str))]
+        daemon: Option<PathBuf>,
+    },
+    Tracer {
+        /// Rewrite the profile every N seconds while tracing (0 disables)
+        #[structopt(long)]
+        checkpoint_interval: Option<u64>,
+        /// Write the profile and stop tracing after N seconds (0 disables)
+        #[structopt(long)]
+        max_duration: Option<u64>,
+    },
+    /// Trace many containers with a single BPF program
+    Daemon {
+        /// Control socket path used by the hooks
+        #[structopt(short, long, parse(from_os_str), default_value = DEFAULT_DAEMON_SOCKET)]
+        socket: PathBuf,
+        /// Trace mode for every container (perf, aggregate)
+        #[structopt(long, default_value = "perf")]
+        trace_mode: TraceMode,
+        /// Trace cap_capable(9) for containers with the capabilities annotation
+        #[structopt(long)]
+        capabilities: bool,
+        /// Rewrite the profiles every N seconds while tracing (0 disables)
+        #[structopt(long)]
+        checkpoint_interval: Option<u64>,
+        /// Write the profile of a container after N seconds of tracing (0 disables)
+        #[structopt(long)]
+        max_duration: Option<u64>,
+    },
+    /// Query or flush a traced container (status, flush, syscalls)
+    Control {
+        /// Send the request to a tracing daemon instead of the tracer of the container
+        #[structopt(long, parse(from_os_str))]
+        daemon: Option<PathBuf>,
+        command: ControlCommand,
+        container_id: String,
+    },
+    /// Trace a command in a transient cgroup, without a container
+    Run {
+        /// Output path of the seccomp profile
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+        /// Trace mode (perf, aggregate)
+        #[structopt(long, default_value = "perf")]
+        trace_mode: TraceMode,
+        /// Syscall which starts recording (prctl, execve, no_new_privs, cgroup)
+        #[structopt(long, default_value = "execve")]
+        start_trigger: StartTrigger,
+        /// Command and its arguments, after `--`
+        #[structopt(required = true, last = true)]
+        command: Vec<String>,
+    },
+    /// Trace a running container or process until Ctrl-C or the timeout
+    Attach {
+        /// Container id or its prefix, found by its cgroup
+        #[structopt(long, conflicts_with = "pid", required_unless = "pid")]
+        container: Option<String>,
+        /// Process whose cgroup is traced
+        #[structopt(long)]
+        pid: Option<i32>,
+        /// Output path of the seccomp profile
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+        /// Trace mode (perf, aggregate)
+        #[structopt(long, default_value = "perf")]
+        trace_mode: TraceMode,
+        /// Detach after N seconds
+        #[structopt(long)]
+        timeout: Option<u64>,
+    },
 }
 
 pub fn handle_dynamic_analyzer(dynamic: DynamicSubCommand) -> Result<()> {
     match dynamic {
-        DynamicSubCommand::Start {} => run_trace_command()?,
-        DynamicSubCommand::Stop {} => stop_tracing()?,
-        DynamicSubCommand::Tracer {} => trace_command()?,
+        DynamicSubCommand::Start {
+            daemon,
+            checkpoint_interval,
+            max_duration,
+        } => run_hook(HookStage::Prestart, |_, state| match &daemon {
+            Some(socket) => register_command(socket, state),
+            None => run_trace_command(state, checkpoint_interval, max_duration),
+        })?,
+        DynamicSubCommand::Stop { daemon } => {
+            run_hook(HookStage::Poststop, |_, state| match &daemon {
+                Some(socket) => unregister_command(socket, state),
+                None => stop_tracing(state),
+            })?
+        }
+        DynamicSubCommand::Tracer {
+            checkpoint_interval,
+            max_duration,
+        } => run_hook(HookStage::Tracer, |config, state| {
+            trace_command(config, state, checkpoint_interval, max_duration)
+        })?,
+        DynamicSubCommand::Daemon {
+            socket,
+            trace_mode,
+            capabilities,
+            checkpoint_interval,
+            max_duration,
+        } => daemon_command(
+            &socket,
+            trace_mode,
+            capabilities,
+            checkpoint_interval,
+            max_duration,
+        )?,
+        DynamicSubCommand::Control {
+            daemon,
+            command,
+            container_id,
+        } => control_command(daemon.as_deref(), command, container_id)?,
+        DynamicSubCommand::Run {
+            output,
+            trace_mode,
+            start_trigger,
+            command,
+        } => run_command(output, trace_mode, start_trigger, command)?,
+        DynamicSubCommand::Attach {
+            container,
+            pid,
+            output,
+            trace_mode,
+            timeout,
+        } => attach_command(container, pid, output, trace_mode, timeout)?,
     }
     Ok(())
 }
 
-fn run_trace_command() -> anyhow::Result<()> {
-    Command::new("/proc/self/exe")
-        .arg("dynamic")
-        .arg("tracer")
-        .stdin(Stdio::inherit())
+/// Spawn the tracer in the background, it reads the container state from its stdin
+fn run_trace_command(
+    state: &State,
+    checkpoint_interval: Option<u64>,
+    max_duration: Option<u64>,
+) -> anyhow::Result<()> {
+    let mut command = Command::new("/proc/self/exe");
+    command.arg("dynamic").arg("tracer");
+    if let Some(interval) = checkpoint_interval {
+        command.arg(format!("--checkpoint-interval={}", interval));
+    }
+    if let Some(duration) = max_duration {
+        command.arg(format!("--max-duration={}", duration));
+    }
+
+    let mut tracer = command
+        .stdin(Stdio::piped())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
-        .spawn()?;
+        .spawn()
+        .context("failed to spawn the tracer")?;
 
-    Ok(())
+    let stdin = tracer.stdin.take().context("no stdin of the tracer")?;
+    serde_json::to_writer(stdin, state).context("failed to pass the state to the tracer")?;
+
+    // the container must not hit the start trigger before the BPF programs are attached
+    wait_for_tracer(&mut tracer, &state.id)
+}
+
+/// How long the prestart hook waits for the tracer to load the BPF programs
+const TRACER_READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Poll the control socket of the tracer until it answers a status request, which
+/// it does once the container is registered
+fn wait_for_tracer(tracer: &mut Child, id: &str) -> Result<()> {
+    let socket = control::tracer_socket_path(id);
+    let request = Request::Status { id: id.to_string() };
+    let deadline = Instant::now() + TRACER_READY_TIMEOUT;
+
+    loop {
+        // a zero read timeout is rejected
+        let timeout = deadline
+            .saturating_duration_since(Instant::now())
+            .max(Duration::from_millis(100));
+        let e = match control::send_request_timeout(&socket, &request, timeout) {
+            Ok(_) => return Ok(()),
+            Err(e) => e,
+        };
+        if let Some(status) = tracer.try_wait()? {
+            bail!("the tracer exited with {}", status);
+        }
+        if Instant::now() >= deadline {
+            return Err(e).context("the tracer did not start in time");
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
 }
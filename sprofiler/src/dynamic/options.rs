@@ -0,0 +1,173 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use serde::Serialize;
+
+/// How the BPF program hands observed syscalls to userspace
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceMode {
+    /// Send every syscall through the perf event buffer
+    PerfEvent,
+    /// Count syscalls in a BPF hash map which is read when tracing stops
+    Aggregate,
+}
+
+impl Default for TraceMode {
+    fn default() -> Self {
+        TraceMode::PerfEvent
+    }
+}
+
+impl FromStr for TraceMode {
+    type Err = anyhow::Error;
+
+    #[rustfmt::skip]
+    fn from_str(mode: &str) -> Result<TraceMode, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "perf" | "perf_event" => Ok(TraceMode::PerfEvent),
+            "aggregate"           => Ok(TraceMode::Aggregate),
+            _                     => bail!("unknown trace mode: {}", mode),
+        }
+    }
+}
+
+/// Syscall which starts recording in a container, must match
+/// `enum START_TRIGGER` in systrace.bpf.c
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum StartTrigger {
+    /// First prctl(2), which crun and runc call while setting up the container
+    Prctl = 0,
+    /// First execve(2), the container entrypoint
+    Execve = 1,
+    /// First seccomp(2), prctl(PR_SET_SECCOMP) or prctl(PR_SET_NO_NEW_PRIVS)
+    NoNewPrivs = 2,
+    /// Every syscall since the cgroup is registered, including the runtime setup
+    Cgroup = 3,
+}
+
+impl Default for StartTrigger {
+    fn default() -> Self {
+        StartTrigger::Prctl
+    }
+}
+
+impl FromStr for StartTrigger {
+    type Err = anyhow::Error;
+
+    #[rustfmt::skip]
+    fn from_str(trigger: &str) -> Result<StartTrigger, Self::Err> {
+        match trigger.to_lowercase().as_str() {
+            "prctl"                   => Ok(StartTrigger::Prctl),
+            "execve"                  => Ok(StartTrigger::Execve),
+            "no_new_privs" | "seccomp" => Ok(StartTrigger::NoNewPrivs),
+            "cgroup"                  => Ok(StartTrigger::Cgroup),
+            _                         => bail!("unknown start trigger: {}", trigger),
+        }
+    }
+}
+
+/// What to do with syscalls which were observed but never succeeded, e.g. a
+/// libc probing for clone3(2) and falling back to clone(2)
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailedSyscalls {
+    /// Allow them like any other observed syscall
+    Allow,
+    /// Leave them out of the profile, the default action applies
+    Drop,
+    /// Return the observed errno instead of calling them
+    Errno,
+}
+
+impl Default for FailedSyscalls {
+    fn default() -> Self {
+        FailedSyscalls::Allow
+    }
+}
+
+impl FromStr for FailedSyscalls {
+    type Err = anyhow::Error;
+
+    #[rustfmt::skip]
+    fn from_str(policy: &str) -> Result<FailedSyscalls, Self::Err> {
+        match policy.to_lowercase().as_str() {
+            "allow" => Ok(FailedSyscalls::Allow),
+            "drop"  => Ok(FailedSyscalls::Drop),
+            "errno" => Ok(FailedSyscalls::Errno),
+            _       => bail!("unknown failed syscalls policy: {}", policy),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trace_mode() {
+        assert_eq!(TraceMode::from_str("perf").unwrap(), TraceMode::PerfEvent);
+        assert_eq!(
+            TraceMode::from_str("Aggregate").unwrap(),
+            TraceMode::Aggregate
+        );
+        assert!(TraceMode::from_str("bitmap").is_err());
+    }
+
+    #[test]
+    fn parse_start_trigger_prctl() {
+        assert_eq!(
+            StartTrigger::from_str("prctl").unwrap(),
+            StartTrigger::Prctl
+        );
+        assert_eq!(StartTrigger::default(), StartTrigger::Prctl);
+    }
+
+    #[test]
+    fn parse_start_trigger_execve() {
+        assert_eq!(
+            StartTrigger::from_str("execve").unwrap(),
+            StartTrigger::Execve
+        );
+        assert_eq!(StartTrigger::Execve as u32, 1);
+    }
+
+    #[test]
+    fn parse_start_trigger_no_new_privs() {
+        assert_eq!(
+            StartTrigger::from_str("no_new_privs").unwrap(),
+            StartTrigger::NoNewPrivs
+        );
+        assert_eq!(
+            StartTrigger::from_str("seccomp").unwrap(),
+            StartTrigger::NoNewPrivs
+        );
+        assert_eq!(StartTrigger::NoNewPrivs as u32, 2);
+    }
+
+    #[test]
+    fn parse_start_trigger_cgroup() {
+        assert_eq!(
+            StartTrigger::from_str("cgroup").unwrap(),
+            StartTrigger::Cgroup
+        );
+        assert_eq!(StartTrigger::Cgroup as u32, 3);
+        assert!(StartTrigger::from_str("fork").is_err());
+    }
+
+    #[test]
+    fn parse_failed_syscalls() {
+        assert_eq!(
+            FailedSyscalls::from_str("drop").unwrap(),
+            FailedSyscalls::Drop
+        );
+        assert_eq!(
+            FailedSyscalls::from_str("Errno").unwrap(),
+            FailedSyscalls::Errno
+        );
+        assert_eq!(FailedSyscalls::default(), FailedSyscalls::Allow);
+        assert!(FailedSyscalls::from_str("kill").is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use nix::errno::Errno;
+use nix::fcntl::{self, OFlag};
+use nix::sys::stat::{self, Mode};
+use nix::unistd::{self, Gid, Uid, UnlinkatFlags};
+use serde::Serialize;
+
+/// Where the files of a profile may be written and with which owner and permissions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputOptions {
+    /// Annotation paths must be below this directory, which is walked without
+    /// following symlinks. Any path is accepted when None.
+    pub root: Option<PathBuf>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Permissions of the written files, e.g. 0o640
+    pub mode: Option<u32>,
+}
+
+impl OutputOptions {
+    /// Check an output path given by a container, a relative path is below the root
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf> {
+        let root = match self.root.as_ref() {
+            Some(root) => root,
+            None => return Ok(path.to_path_buf()),
+        };
+
+        if path
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            bail!("output path {} must not contain ..", path.display());
+        }
+        if path.file_name().is_none() {
+            bail!("output path {} is not a file", path.display());
+        }
+
+        if path.is_relative() {
+            return Ok(root.join(path));
+        }
+        if !path.starts_with(root) {
+            bail!(
+                "output path {} is not below {}",
+                path.display(),
+                root.display()
+            );
+        }
+        Ok(path.to_path_buf())
+    }
+
+    /// Open the parent directory of `path`, below the root no symlink is followed
+    /// and missing directories are created
+    fn open_parent(&self, path: &Path) -> Result<File> {
+        let flags = OFlag::O_DIRECTORY | OFlag::O_RDONLY | OFlag::O_CLOEXEC;
+        let parent = match path.parent() {
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+
+        let (root, below) = match self
+            .root
+            .as_ref()
+            .and_then(|root| Some((root, parent.strip_prefix(root).ok()?)))
+        {
+            Some((root, below)) => {
+                // the root is set by the administrator, symlinks above it are trusted
+                fs::create_dir_all(root)
+                    .with_context(|| format!("failed to create {}", root.display()))?;
+                (root.as_path(), below)
+            }
+            None => (parent, Path::new("")),
+        };
+
+        let fd = fcntl::open(root, flags, Mode::empty())
+            .with_context(|| format!("failed to open {}", root.display()))?;
+        let mut dir = unsafe { File::from_raw_fd(fd) };
+
+        for component in below.components() {
+            let name = match component {
+                Component::Normal(name) => name,
+                _ => continue,
+            };
+            match stat::mkdirat(dir.as_raw_fd(), name, Mode::from_bits_truncate(0o755)) {
+                Ok(()) | Err(Errno::EEXIST) => {}
+                Err(e) => return Err(e).with_context(|| format!("failed to create {:?}", name)),
+            }
+            let fd = fcntl::openat(
+                dir.as_raw_fd(),
+                name,
+                flags | OFlag::O_NOFOLLOW,
+                Mode::empty(),
+            )
+            .with_context(|| format!("failed to open {} without symlinks", parent.display()))?;
+            dir = unsafe { File::from_raw_fd(fd) };
+        }
+
+        Ok(dir)
+    }
+
+    /// Create `name` in `dir`, a symlink in its place is not followed
+    fn create_at(&self, dir: &File, name: &OsStr, flags: OFlag) -> Result<File> {
+        let mode = Mode::from_bits_truncate(self.mode.unwrap_or(0o644));
+        let fd = fcntl::openat(
+            dir.as_raw_fd(),
+            name,
+            flags | OFlag::O_WRONLY | OFlag::O_CREAT | OFlag::O_NOFOLLOW | OFlag::O_CLOEXEC,
+            mode,
+        )
+        .with_context(|| format!("failed to create {:?}", name))?;
+        let file = unsafe { File::from_raw_fd(fd) };
+
+        if self.uid.is_some() || self.gid.is_some() {
+            unistd::fchown(
+                file.as_raw_fd(),
+                self.uid.map(Uid::from_raw),
+                self.gid.map(Gid::from_raw),
+            )
+            .with_context(|| format!("failed to change the owner of {:?}", name))?;
+        }
+        // the mode given to open(2) is masked by the umask
+        if self.mode.is_some() {
+            stat::fchmod(file.as_raw_fd(), mode)
+                .with_context(|| format!("failed to change the mode of {:?}", name))?;
+        }
+
+        Ok(file)
+    }
+}
+
+fn file_name(path: &Path) -> Result<&OsStr> {
+    path.file_name()
+        .with_context(|| format!("{} is not a file", path.display()))
+}
+
+/// e.g. profile.json -> .profile.json.tmp
+fn temporary_name(file_name: &OsStr) -> OsString {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(".tmp");
+    tmp_name
+}
+
+/// Create or truncate `path`, e.g. for logs written while tracing
+pub fn create_file(path: &Path, options: &OutputOptions) -> Result<File> {
+    let dir = options.open_parent(path)?;
+    options.create_at(&dir, file_name(path)?, OFlag::O_TRUNC)
+}
+
+/// Replace `path` with the JSON of `value`, readers see either the old or the new file
+pub fn write_json_atomic<T: Serialize>(
+    path: &Path,
+    value: &T,
+    options: &OutputOptions,
+) -> Result<()> {
+    let dir = options.open_parent(path)?;
+    let name = file_name(path)?;
+    let tmp_name = temporary_name(name);
+
+    // a leftover of a previous write, or a link planted in its place
+    let _ = unistd::unlinkat(
+        Some(dir.as_raw_fd()),
+        tmp_name.as_os_str(),
+        UnlinkatFlags::NoRemoveDir,
+    );
+    let file = options.create_at(&dir, &tmp_name, OFlag::O_EXCL)?;
+
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, value)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+
+    // rename(2) replaces a symlink at `path` instead of following it
+    fcntl::renameat(
+        Some(dir.as_raw_fd()),
+        tmp_name.as_os_str(),
+        Some(dir.as_raw_fd()),
+        name,
+    )
+    .with_context(|| format!("failed to rename to {}", path.display()))?;
+
+    // the rename survives a crash only once the directory is synced
+    dir.sync_all()
+        .with_context(|| format!("failed to sync the directory of {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sprofiler-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn write_json_atomic_replaces_file() {
+        let dir = test_dir("output");
+        let path = dir.join("seccomp.json");
+        let options = OutputOptions::default();
+
+        write_json_atomic(&path, &vec!["read"], &options).unwrap();
+        write_json_atomic(&path, &vec!["read", "write"], &options).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[\"read\",\"write\"]");
+        assert!(!dir
+            .join(temporary_name(OsStr::new("seccomp.json")))
+            .exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolve_below_root() {
+        let options = OutputOptions {
+            root: Some(PathBuf::from("/var/lib/sprofiler")),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.resolve(Path::new("nginx/seccomp.json")).unwrap(),
+            PathBuf::from("/var/lib/sprofiler/nginx/seccomp.json")
+        );
+        assert_eq!(
+            options
+                .resolve(Path::new("/var/lib/sprofiler/seccomp.json"))
+                .unwrap(),
+            PathBuf::from("/var/lib/sprofiler/seccomp.json")
+        );
+        assert!(options.resolve(Path::new("/etc/shadow")).is_err());
+        assert!(options
+            .resolve(Path::new("/var/lib/sprofiler/../../../etc/shadow"))
+            .is_err());
+        assert!(options.resolve(Path::new("../seccomp.json")).is_err());
+        assert!(options
+            .resolve(Path::new("/var/lib/sprofiler-evil/x.json"))
+            .is_err());
+    }
+
+    #[test]
+    fn refuse_symlinks_below_root() {
+        let root = test_dir("output-root");
+        let outside = test_dir("output-outside");
+        symlink(&outside, root.join("link")).unwrap();
+        symlink(outside.join("target.json"), root.join("seccomp.json")).unwrap();
+        let options = OutputOptions {
+            root: Some(root.clone()),
+            mode: Some(0o600),
+            ..Default::default()
+        };
+
+        // a symlinked directory is not walked
+        assert!(write_json_atomic(&root.join("link/seccomp.json"), &1, &options).is_err());
+        assert!(create_file(&root.join("link/events.jsonl"), &options).is_err());
+        // a symlinked file is replaced, its target is left alone
+        write_json_atomic(&root.join("seccomp.json"), &1, &options).unwrap();
+        assert!(!outside.join("target.json").exists());
+        // missing directories are created
+        write_json_atomic(&root.join("a/b/seccomp.json"), &1, &options).unwrap();
+
+        let mode = fs::metadata(root.join("a/b/seccomp.json"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&outside).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::Serialize;
+
+use crate::dynamic::recorder;
+
+/// Syscalls used by each process name (comm) and uid of a container
+#[derive(Default, Debug)]
+pub struct ProcessBreakdown {
+    processes: BTreeMap<(String, u32), BTreeSet<&'static str>>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ProcessSyscalls {
+    pub comm: String,
+    pub uid: u32,
+    pub syscalls: Vec<&'static str>,
+}
+
+/// comm is a NUL terminated string of at most TASK_COMM_LEN bytes
+pub fn comm_to_string(comm: &[u8]) -> String {
+    let len = comm.iter().position(|c| *c == 0).unwrap_or(comm.len());
+    String::from_utf8_lossy(&comm[..len]).to_string()
+}
+
+impl ProcessBreakdown {
+    pub fn record(&mut self, comm: &[u8], uid: u32, syscall_nr: i64, compat: bool) {
+        let syscall_name = match recorder::syscall_name(syscall_nr, compat) {
+            Some(syscall_name) => syscall_name,
+            None => return,
+        };
+
+        self.processes
+            .entry((comm_to_string(comm), uid))
+            .or_default()
+            .insert(syscall_name);
+    }
+
+    pub fn report(&self) -> Vec<ProcessSyscalls> {
+        self.processes
+            .iter()
+            .map(|((comm, uid), syscalls)| ProcessSyscalls {
+                comm: comm.clone(),
+                uid: *uid,
+                syscalls: syscalls.iter().copied().collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comm(name: &str) -> [u8; 32] {
+        let mut comm = [0; 32];
+        comm[..name.len()].copy_from_slice(name.as_bytes());
+        comm
+    }
+
+    #[test]
+    fn breakdown_by_comm_and_uid() {
+        let mut breakdown = ProcessBreakdown::default();
+        // healthcheck calls ptrace as root, the app reads and writes as uid 1000
+        breakdown.record(&comm("healthcheck"), 0, 101, false);
+        breakdown.record(&comm("app"), 1000, 1, false);
+        breakdown.record(&comm("app"), 1000, 0, false);
+        breakdown.record(&comm("app"), 0, 0, false);
+
+        assert_eq!(
+            breakdown.report(),
+            vec![
+                ProcessSyscalls {
+                    comm: "app".to_string(),
+                    uid: 0,
+                    syscalls: vec!["read"],
+                },
+                ProcessSyscalls {
+                    comm: "app".to_string(),
+                    uid: 1000,
+                    syscalls: vec!["read", "write"],
+                },
+                ProcessSyscalls {
+                    comm: "healthcheck".to_string(),
+                    uid: 0,
+                    syscalls: vec!["ptrace"],
+                },
+            ]
+        );
+    }
+}
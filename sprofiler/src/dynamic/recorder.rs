@@ -0,0 +1,434 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use oci_runtime_spec::{Arch, LinuxSeccomp, LinuxSeccompAction, LinuxSyscall};
+use sprofiler_sys::arch::{x86, x86_64};
+
+use crate::dynamic::args::{self, ObservedArgs, MAX_SYSCALL_ARGS};
+use crate::dynamic::options::FailedSyscalls;
+
+/// Syscalls whose return value is not the result of the call
+const UNCHECKED_RESULTS: &[&str] = &["rt_sigreturn", "restart_syscall"];
+
+/// Return values of a syscall seen by sys_exit
+#[derive(Default, Debug, Clone, Copy)]
+pub struct SyscallResult {
+    pub successes: u64,
+    pub failures: u64,
+    /// Last errno returned by the syscall
+    pub errno: u32,
+}
+
+pub fn syscall_name(syscall_nr: i64, compat: bool) -> Option<&'static str> {
+    let syscalls = if compat {
+        &*x86::SYSCALLS
+    } else {
+        &*x86_64::SYSCALLS
+    };
+    syscalls.get(&(syscall_nr as u32)).copied()
+}
+
+/// How often a syscall was called, timestamps are nanoseconds since boot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyscallStats {
+    pub count: u64,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+impl SyscallStats {
+    pub fn at(ts: u64) -> Self {
+        SyscallStats {
+            count: 1,
+            first_seen: ts,
+            last_seen: ts,
+        }
+    }
+
+    fn add(&mut self, other: &SyscallStats) {
+        self.count += other.count;
+        self.first_seen = self.first_seen.min(other.first_seen);
+        self.last_seen = self.last_seen.max(other.last_seen);
+    }
+}
+
+/// Syscalls observed in a single container
+#[derive(Default, Debug, Clone)]
+pub struct SyscallRecorder {
+    /// Syscalls called through the x86_64 ABI
+    syscalls: HashSet<&'static str>,
+    /// Syscalls called through the ia32 ABI, whose arguments are not recorded
+    compat_syscalls: HashSet<&'static str>,
+    args: ObservedArgs,
+    results: HashMap<&'static str, SyscallResult>,
+    stats: HashMap<&'static str, SyscallStats>,
+}
+
+impl SyscallRecorder {
+    pub fn record(
+        &mut self,
+        syscall_nr: i64,
+        compat: bool,
+        syscall_args: &[u64; MAX_SYSCALL_ARGS],
+    ) {
+        let syscall_name = match syscall_name(syscall_nr, compat) {
+            Some(syscall_name) => syscall_name,
+            None => return,
+        };
+
+        if compat {
+            self.compat_syscalls.insert(syscall_name);
+            return;
+        }
+        self.syscalls.insert(syscall_name);
+
+        if args::is_arg_filtered(syscall_name) {
+            let values = args::filtered_values(syscall_name, syscall_args);
+            self.args.entry(syscall_name).or_default().insert(values);
+        }
+    }
+
+    pub fn record_result(&mut self, syscall_nr: i64, compat: bool, result: &SyscallResult) {
+        let syscall_name = match syscall_name(syscall_nr, compat) {
+            Some(syscall_name) => syscall_name,
+            None => return,
+        };
+
+        let total = self.results.entry(syscall_name).or_default();
+        total.successes += result.successes;
+        total.failures += result.failures;
+        if result.failures > 0 {
+            total.errno = result.errno;
+        }
+    }
+
+    pub fn record_stats(&mut self, syscall_nr: i64, compat: bool, stats: &SyscallStats) {
+        let syscall_name = match syscall_name(syscall_nr, compat) {
+            Some(syscall_name) => syscall_name,
+            None => return,
+        };
+
+        self.stats
+            .entry(syscall_name)
+            .and_modify(|total| total.add(stats))
+            .or_insert(*stats);
+    }
+
+    /// Call counts by syscall name, the most frequent first
+    pub fn stats(&self) -> Vec<(&'static str, SyscallStats)> {
+        let mut stats: Vec<(&'static str, SyscallStats)> = self
+            .stats
+            .iter()
+            .map(|(name, stats)| (*name, *stats))
+            .collect();
+        stats.sort_unstable_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(b.0)));
+        stats
+    }
+
+    /// Add the observations of `other`, e.g. another phase of the same container
+    pub fn merge(&mut self, other: &SyscallRecorder) {
+        self.syscalls.extend(&other.syscalls);
+        self.compat_syscalls.extend(&other.compat_syscalls);
+        for (syscall_name, values) in &other.args {
+            self.args
+                .entry(syscall_name)
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+        for (syscall_name, result) in &other.results {
+            let total = self.results.entry(syscall_name).or_default();
+            total.successes += result.successes;
+            total.failures += result.failures;
+            if result.failures > 0 {
+                total.errno = result.errno;
+            }
+        }
+        for (syscall_name, stats) in &other.stats {
+            self.stats
+                .entry(syscall_name)
+                .and_modify(|total| total.add(stats))
+                .or_insert(*stats);
+        }
+    }
+
+    /// Syscalls of both ABIs
+    fn all_names(&self) -> HashSet<&'static str> {
+        self.syscalls
+            .union(&self.compat_syscalls)
+            .copied()
+            .collect()
+    }
+
+    pub fn syscall_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.all_names().into_iter().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Syscalls called through the ia32 ABI
+    pub fn compat_syscall_names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.compat_syscalls.iter().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Syscalls recorded here but not in `other`
+    pub fn difference(&self, other: &SyscallRecorder) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self
+            .all_names()
+            .difference(&other.all_names())
+            .copied()
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn never_succeeded(&self, syscall_name: &str) -> Option<&SyscallResult> {
+        if UNCHECKED_RESULTS.contains(&syscall_name) {
+            return None;
+        }
+        self.results
+            .get(syscall_name)
+            .filter(|result| result.successes == 0 && result.failures > 0)
+    }
+
+    /// Observed syscalls which returned an error every time they were called
+    pub fn failed_syscalls(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self
+            .all_names()
+            .into_iter()
+            .filter(|s| self.never_succeeded(s).is_some())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn seccomp_profile(&self, failed_syscalls: FailedSyscalls) -> LinuxSeccomp {
+        let is_dropped =
+            |s: &str| failed_syscalls != FailedSyscalls::Allow && self.never_succeeded(s).is_some();
+        // argument rules are only generated for the x86_64 ABI
+        let is_arg_filtered =
+            |s: &str| self.args.contains_key(s) && !self.compat_syscalls.contains(s);
+
+        // the rules of an OCI profile apply to every architecture, an ia32 syscall
+        // is allowed for x86_64 too, see compat_syscall_names
+        let mut names: Vec<String> = self
+            .all_names()
+            .iter()
+            .filter(|s| !is_arg_filtered(s) && !is_dropped(s))
+            .map(|s| s.to_string())
+            .collect();
+        names.sort();
+
+        let mut syscalls = vec![LinuxSyscall {
+            names,
+            action: LinuxSeccompAction::SCMP_ACT_ALLOW,
+            args: None,
+            errno_ret: None,
+        }];
+
+        let mut args = self.args.clone();
+        args.retain(|s, _| is_arg_filtered(s) && !is_dropped(s));
+        syscalls.extend(args::gen_arg_rules(&args));
+
+        if failed_syscalls == FailedSyscalls::Errno {
+            let mut errnos: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+            for name in self.failed_syscalls() {
+                let errno = self.results[name].errno;
+                errnos.entry(errno).or_default().push(name.to_string());
+            }
+            syscalls.extend(errnos.into_iter().map(|(errno, names)| LinuxSyscall {
+                names,
+                action: LinuxSeccompAction::SCMP_ACT_ERRNO,
+                args: None,
+                errno_ret: Some(errno),
+            }));
+        }
+
+        let mut architectures = vec![Arch::SCMP_ARCH_X86_64];
+        if !self.compat_syscalls.is_empty() {
+            architectures.push(Arch::SCMP_ARCH_X86);
+        }
+
+        LinuxSeccomp {
+            syscalls: Some(syscalls),
+            default_action: LinuxSeccompAction::SCMP_ACT_ERRNO,
+            architectures: Some(architectures),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NO_ARGS: [u64; MAX_SYSCALL_ARGS] = [0; MAX_SYSCALL_ARGS];
+
+    #[test]
+    fn seccomp_profile_sorted_names() {
+        let mut recorder = SyscallRecorder::default();
+        // write, read, write
+        recorder.record(1, false, &NO_ARGS);
+        recorder.record(0, false, &NO_ARGS);
+        recorder.record(1, false, &NO_ARGS);
+
+        let profile = recorder.seccomp_profile(FailedSyscalls::Allow);
+        let syscalls = profile.syscalls.unwrap();
+
+        assert_eq!(syscalls.len(), 1);
+        assert_eq!(syscalls[0].names, vec!["read", "write"]);
+        assert_eq!(syscalls[0].action, LinuxSeccompAction::SCMP_ACT_ALLOW);
+    }
+
+    #[test]
+    fn seccomp_profile_argument_filtered_syscall() {
+        let mut recorder = SyscallRecorder::default();
+        // socket(AF_INET, SOCK_STREAM, 0) and socket(AF_INET6, SOCK_STREAM, 0)
+        recorder.record(41, false, &[2, 1, 0, 0, 0, 0]);
+        recorder.record(41, false, &[10, 1, 0, 0, 0, 0]);
+        recorder.record(0, false, &NO_ARGS);
+
+        let profile = recorder.seccomp_profile(FailedSyscalls::Allow);
+        let syscalls = profile.syscalls.unwrap();
+
+        assert_eq!(syscalls.len(), 3);
+        assert_eq!(syscalls[0].names, vec!["read"]);
+        assert!(syscalls[1..]
+            .iter()
+            .all(|s| s.names == vec!["socket"] && s.args.is_some()));
+    }
+
+    #[test]
+    fn unknown_syscall_number_is_ignored() {
+        let mut recorder = SyscallRecorder::default();
+        recorder.record(-1, false, &NO_ARGS);
+
+        let profile = recorder.seccomp_profile(FailedSyscalls::Allow);
+        assert!(profile.syscalls.unwrap()[0].names.is_empty());
+    }
+
+    #[test]
+    fn seccomp_profile_failed_syscalls() {
+        let mut recorder = SyscallRecorder::default();
+        // clone3 always fails with ENOSYS, clone succeeds, statx fails once
+        recorder.record(435, false, &NO_ARGS);
+        recorder.record(56, false, &NO_ARGS);
+        recorder.record(332, false, &NO_ARGS);
+        let failed = SyscallResult {
+            successes: 0,
+            failures: 1,
+            errno: 38,
+        };
+        let succeeded = SyscallResult {
+            successes: 1,
+            failures: 0,
+            errno: 0,
+        };
+        recorder.record_result(435, false, &failed);
+        recorder.record_result(56, false, &succeeded);
+        recorder.record_result(332, false, &failed);
+        recorder.record_result(332, false, &succeeded);
+
+        assert_eq!(recorder.failed_syscalls(), vec!["clone3"]);
+
+        let allow = recorder.seccomp_profile(FailedSyscalls::Allow);
+        let names: Vec<&str> = allow.syscalls.as_ref().unwrap()[0]
+            .names
+            .iter()
+            .map(String::as_str)
+            .collect();
+        assert_eq!(names, vec!["clone3", "statx"]);
+
+        let drop = recorder.seccomp_profile(FailedSyscalls::Drop);
+        let syscalls = drop.syscalls.unwrap();
+        assert_eq!(syscalls[0].names, vec!["statx"]);
+        assert!(syscalls
+            .iter()
+            .all(|s| !s.names.contains(&"clone3".to_string())));
+
+        let errno = recorder.seccomp_profile(FailedSyscalls::Errno);
+        let syscalls = errno.syscalls.unwrap();
+        let last = syscalls.last().unwrap();
+        assert_eq!(last.names, vec!["clone3"]);
+        assert_eq!(last.action, LinuxSeccompAction::SCMP_ACT_ERRNO);
+        assert_eq!(last.errno_ret, Some(38));
+    }
+
+    #[test]
+    fn seccomp_profile_compat_syscalls() {
+        let mut recorder = SyscallRecorder::default();
+        // ia32 102 is socketcall, not getuid as on x86_64
+        recorder.record(0, false, &NO_ARGS);
+        recorder.record(102, true, &NO_ARGS);
+        // ia32 socket(2) is allowed without argument rules
+        recorder.record(359, true, &NO_ARGS);
+        // read is 3 on ia32
+        recorder.record(3, true, &NO_ARGS);
+
+        assert_eq!(
+            recorder.compat_syscall_names(),
+            vec!["read", "socket", "socketcall"]
+        );
+        assert_eq!(
+            recorder.syscall_names(),
+            vec!["read", "socket", "socketcall"]
+        );
+        let mut native = SyscallRecorder::default();
+        native.record(0, false, &NO_ARGS);
+        assert_eq!(recorder.difference(&native), vec!["socket", "socketcall"]);
+
+        let profile = recorder.seccomp_profile(FailedSyscalls::Allow);
+        assert_eq!(
+            profile.architectures.unwrap(),
+            vec![Arch::SCMP_ARCH_X86_64, Arch::SCMP_ARCH_X86]
+        );
+        let syscalls = profile.syscalls.unwrap();
+        assert_eq!(syscalls.len(), 1);
+        assert_eq!(syscalls[0].names, vec!["read", "socket", "socketcall"]);
+    }
+
+    #[test]
+    fn merge_phases() {
+        let mut startup = SyscallRecorder::default();
+        // chroot, read
+        startup.record(161, false, &NO_ARGS);
+        startup.record(0, false, &NO_ARGS);
+        let mut steady = SyscallRecorder::default();
+        // read, write
+        steady.record(0, false, &NO_ARGS);
+        steady.record(1, false, &NO_ARGS);
+
+        assert_eq!(startup.difference(&steady), vec!["chroot"]);
+
+        let mut all = startup.clone();
+        all.merge(&steady);
+        let profile = all.seccomp_profile(FailedSyscalls::Allow);
+        assert_eq!(
+            profile.syscalls.unwrap()[0].names,
+            vec!["chroot", "read", "write"]
+        );
+    }
+
+    #[test]
+    fn stats_sorted_by_count() {
+        let mut recorder = SyscallRecorder::default();
+        recorder.record_stats(0, false, &SyscallStats::at(30));
+        recorder.record_stats(0, false, &SyscallStats::at(10));
+        recorder.record_stats(1, false, &SyscallStats::at(20));
+        let mut steady = SyscallRecorder::default();
+        let write_stats = SyscallStats {
+            count: 5,
+            first_seen: 40,
+            last_seen: 50,
+        };
+        steady.record_stats(1, false, &write_stats);
+        recorder.merge(&steady);
+
+        let stats = recorder.stats();
+        assert_eq!(stats[0].0, "write");
+        assert_eq!(stats[0].1.count, 6);
+        assert_eq!((stats[0].1.first_seen, stats[0].1.last_seen), (20, 50));
+        assert_eq!(stats[1].0, "read");
+        assert_eq!(stats[1].1.count, 2);
+        assert_eq!((stats[1].1.first_seen, stats[1].1.last_seen), (10, 30));
+    }
+}
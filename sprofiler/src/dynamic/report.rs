@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::dynamic::call_site::CallSite;
+use crate::dynamic::options::{FailedSyscalls, StartTrigger, TraceMode};
+use crate::dynamic::recorder::SyscallStats;
+
+/// How a profile was recorded, written next to the seccomp profile
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TraceReport {
+    pub container_id: String,
+    pub trace_mode: TraceMode,
+    pub start_trigger: StartTrigger,
+    pub failed_syscalls_policy: FailedSyscalls,
+    /// Observed syscalls which never succeeded
+    pub failed_syscalls: Vec<&'static str>,
+    /// Written while tracing, the container was still running
+    pub checkpoint: bool,
+    /// Seconds from the registration to the steady state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub steady_state_after: Option<u64>,
+    /// Syscalls which were not called after the startup phase
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup_only_syscalls: Option<Vec<&'static str>>,
+    /// Why each syscall is needed, the stack of its first call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_sites: Option<Vec<CallSite>>,
+    /// Capabilities which were checked but not held by the container
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denied_capabilities: Option<Vec<&'static str>>,
+    /// Syscalls called through the ia32 ABI, the profile allows them for x86_64 too
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ia32_syscalls: Vec<&'static str>,
+}
+
+/// How often a syscall was called, written to `<profile>.stats.json`
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SyscallFrequency {
+    pub name: &'static str,
+    pub count: u64,
+    /// Seconds since the first traced syscall
+    pub first_seen: f64,
+    pub last_seen: f64,
+}
+
+/// Frequencies of `stats`, timestamps are relative to the earliest one
+pub fn syscall_frequencies(stats: &[(&'static str, SyscallStats)]) -> Vec<SyscallFrequency> {
+    let start = stats
+        .iter()
+        .map(|(_, stats)| stats.first_seen)
+        .min()
+        .unwrap_or_default();
+    let seconds = |ts: u64| ts.saturating_sub(start) as f64 / 1e9;
+
+    stats
+        .iter()
+        .map(|(name, stats)| SyscallFrequency {
+            name,
+            count: stats.count,
+            first_seen: seconds(stats.first_seen),
+            last_seen: seconds(stats.last_seen),
+        })
+        .collect()
+}
+
+/// Phase of the container lifetime with its own profile
+#[derive(Clone, Copy, Debug)]
+pub enum Phase {
+    Startup,
+    SteadyState,
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.report.json
+pub fn report_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("report.json")
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.startup.json
+pub fn phase_profile_path(profile_path: &Path, phase: Phase) -> PathBuf {
+    let extension = match phase {
+        Phase::Startup => "startup.json",
+        Phase::SteadyState => "steady.json",
+    };
+    profile_path.with_extension(extension)
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.processes.json
+pub fn process_report_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("processes.json")
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.stats.json
+pub fn stats_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("stats.json")
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.error.json
+pub fn error_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("error.json")
+}
+
+/// e.g. /path/to/profile.json -> /path/to/profile.events.jsonl
+pub fn event_log_path(profile_path: &Path) -> PathBuf {
+    profile_path.with_extension("events.jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_path_replace_extension() {
+        assert_eq!(
+            report_path(Path::new("/tmp/seccomp.json")),
+            PathBuf::from("/tmp/seccomp.report.json")
+        );
+        assert_eq!(
+            report_path(Path::new("/tmp/seccomp")),
+            PathBuf::from("/tmp/seccomp.report.json")
+        );
+    }
+
+    #[test]
+    fn phase_profile_path_replace_extension() {
+        assert_eq!(
+            phase_profile_path(Path::new("/tmp/seccomp.json"), Phase::Startup),
+            PathBuf::from("/tmp/seccomp.startup.json")
+        );
+        assert_eq!(
+            phase_profile_path(Path::new("/tmp/seccomp.json"), Phase::SteadyState),
+            PathBuf::from("/tmp/seccomp.steady.json")
+        );
+    }
+
+    #[test]
+    fn syscall_frequencies_relative_to_first_seen() {
+        let stats = [
+            (
+                "write",
+                SyscallStats {
+                    count: 3,
+                    first_seen: 2_000_000_000,
+                    last_seen: 4_500_000_000,
+                },
+            ),
+            ("execve", SyscallStats::at(1_000_000_000)),
+        ];
+
+        assert_eq!(
+            syscall_frequencies(&stats),
+            vec![
+                SyscallFrequency {
+                    name: "write",
+                    count: 3,
+                    first_seen: 1.0,
+                    last_seen: 3.5,
+                },
+                SyscallFrequency {
+                    name: "execve",
+                    count: 1,
+                    first_seen: 0.0,
+                    last_seen: 0.0,
+                },
+            ]
+        );
+    }
+}
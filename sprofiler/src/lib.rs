@@ -1,5 +1,6 @@
 pub mod bpf;
 pub mod command;
 pub mod dynamic;
+pub mod profile;
 pub mod profile_util;
 pub mod r#static;
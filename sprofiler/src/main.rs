@@ -2,6 +2,7 @@ use anyhow::Result;
 use structopt::StructOpt;
 
 use sprofiler::dynamic::{handle_dynamic_analyzer, DynamicSubCommand};
+use sprofiler::profile::{handle_profile, ProfileSubCommand};
 use sprofiler::r#static::{handle_static_analyzer, StaticSubCommand};
 
 #[derive(Debug, StructOpt)]
@@ -9,6 +10,7 @@ use sprofiler::r#static::{handle_static_analyzer, StaticSubCommand};
 enum SprofilerCommand {
     Static(StaticSubCommand),
     Dynamic(DynamicSubCommand),
+    Profile(ProfileSubCommand),
 }
 
 fn main() -> Result<()> {
@@ -17,6 +19,7 @@ fn main() -> Result<()> {
     match sprofiler_cmd {
         SprofilerCommand::Static(static_) => handle_static_analyzer(static_)?,
         SprofilerCommand::Dynamic(dynamic) => handle_dynamic_analyzer(dynamic)?,
+        SprofilerCommand::Profile(profile) => handle_profile(profile)?,
     }
 
     Ok(())
@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use structopt::StructOpt;
+
+use crate::dynamic::event_log::{self, EventFilter};
+use crate::dynamic::options::FailedSyscalls;
+use crate::dynamic::output::{self, OutputOptions};
+use crate::dynamic::recorder::{SyscallRecorder, SyscallStats};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "profile", about = "Profile Generator")]
+pub enum ProfileSubCommand {
+    /// Rebuild a seccomp profile from the event log of the dynamic analyzer
+    FromLog {
+        /// Event log (<profile>.events.jsonl)
+        #[structopt(parse(from_os_str))]
+        log: PathBuf,
+        /// Output seccomp profile path
+        #[structopt(short, long, parse(from_os_str))]
+        output: PathBuf,
+        /// Only use the events of these process names
+        #[structopt(long)]
+        comm: Vec<String>,
+        /// Only use the events of these uids
+        #[structopt(long)]
+        uid: Vec<u32>,
+        /// Skip the events of the first N seconds of the log
+        #[structopt(long)]
+        since: Option<f64>,
+        /// Skip the events after N seconds from the start of the log
+        #[structopt(long)]
+        until: Option<f64>,
+    },
+}
+
+fn open_log(log: &Path) -> Result<BufReader<File>> {
+    let file = File::open(log).with_context(|| format!("failed to open {}", log.display()))?;
+    Ok(BufReader::new(file))
+}
+
+pub fn profile_from_log(log: &Path, output: &Path, filter: &EventFilter) -> Result<()> {
+    // the time filters are relative to the earliest event, which may be logged later
+    let start_ts = event_log::earliest_ts(open_log(log)?)?.unwrap_or_default();
+
+    let mut recorder = SyscallRecorder::default();
+    let mut used = 0;
+
+    for record in event_log::read_events(open_log(log)?) {
+        let record = record?;
+        if !filter.matches(&record, start_ts) {
+            continue;
+        }
+
+        recorder.record(record.nr, record.compat, &record.args.unwrap_or_default());
+        recorder.record_stats(record.nr, record.compat, &SyscallStats::at(record.ts));
+        used += 1;
+    }
+
+    output::write_json_atomic(
+        output,
+        &recorder.seccomp_profile(FailedSyscalls::Allow),
+        &OutputOptions::default(),
+    )?;
+    println!(
+        "{} events used, profile written to {}",
+        used,
+        output.display()
+    );
+
+    Ok(())
+}
+
+pub fn handle_profile(profile: ProfileSubCommand) -> Result<()> {
+    match profile {
+        ProfileSubCommand::FromLog {
+            log,
+            output,
+            comm,
+            uid,
+            since,
+            until,
+        } => {
+            let filter = EventFilter {
+                comms: comm,
+                uids: uid,
+                since,
+                until,
+            };
+            profile_from_log(&log, &output, &filter)?
+        }
+    }
+    Ok(())
+}
@@ -68,6 +68,7 @@ pub fn merge(profiles: Vec<LinuxSeccomp>) -> LinuxSeccomp {
             names,
             action: LinuxSeccompAction::SCMP_ACT_ALLOW,
             args: None,
+            errno_ret: None,
         }]),
     }
 }
@@ -109,6 +110,7 @@ mod tests {
                 names,
                 action: LinuxSeccompAction::SCMP_ACT_ALLOW,
                 args: None,
+                errno_ret: None,
             }]),
         }
     }
@@ -121,6 +123,7 @@ mod tests {
             names,
             action: LinuxSeccompAction::SCMP_ACT_ALLOW,
             args: None,
+            errno_ret: None,
         };
 
         LinuxSeccomp {
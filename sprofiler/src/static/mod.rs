@@ -1,9 +1,9 @@
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use anyhow::Result;
-use sprofiler_sys::lang::{Language, SeccompProfilerBuilder};
+use anyhow::{Context, Result};
+use sprofiler_sys::lang::{libc, Language, SeccompProfilerBuilder};
 use structopt::StructOpt;
 
 use crate::profile_util::{self, DiffStatus};
@@ -19,12 +19,25 @@ pub enum StaticSubCommand {
         /// Output seccomp profile path
         #[structopt(short, long, parse(from_os_str))]
         out: PathBuf,
-        /// Mapped data for function to syscall name
+        /// Mapped data for function to syscall name, extends the bundled map of glibc or musl
         #[structopt(short = "m", long = "map", parse(from_os_str))]
         map: Option<PathBuf>,
         /// Analyze Binary Language (e.g. c, go)
         #[structopt(short, long)]
         lang: String,
+        /// Root filesystem of an image, the binary is a path in it and its
+        /// shared libraries are analyzed too
+        #[structopt(long, parse(from_os_str))]
+        rootfs: Option<PathBuf>,
+    },
+    /// Generate a function to syscall map by analyzing a libc shared object
+    GenMap {
+        /// libc shared object, e.g. /lib/x86_64-linux-gnu/libc.so.6
+        #[structopt(long, parse(from_os_str))]
+        libc: PathBuf,
+        /// Output map path
+        #[structopt(short, long, parse(from_os_str))]
+        out: PathBuf,
     },
     /// Output the difference between the two profiles
     Diff {
@@ -44,13 +57,31 @@ pub enum StaticSubCommand {
     },
 }
 
-pub fn do_run(bin: PathBuf, out: PathBuf, map: Option<PathBuf>, lang: &str) -> Result<()> {
+pub fn do_run(
+    bin: PathBuf,
+    out: PathBuf,
+    map: Option<PathBuf>,
+    lang: &str,
+    rootfs: Option<PathBuf>,
+) -> Result<()> {
+    // a binary given by its host path below the root filesystem
+    let bin = match &rootfs {
+        Some(rootfs) => match bin.strip_prefix(rootfs) {
+            Ok(path) => Path::new("/").join(path),
+            Err(_) => bin,
+        },
+        None => bin,
+    };
+
     let mut sprofiler_builder =
         SeccompProfilerBuilder::new(bin, out, Language::from_str(lang).unwrap());
 
     if let Some(map) = map {
         sprofiler_builder.set_syscall_map(map);
     }
+    if let Some(rootfs) = rootfs {
+        sprofiler_builder.set_rootfs(rootfs);
+    }
 
     let sprofiler = sprofiler_builder.build();
     sprofiler.output()?;
@@ -58,6 +89,18 @@ pub fn do_run(bin: PathBuf, out: PathBuf, map: Option<PathBuf>, lang: &str) -> R
     Ok(())
 }
 
+pub fn do_gen_map(libc_path: PathBuf, out: PathBuf) -> Result<()> {
+    let data =
+        fs::read(&libc_path).with_context(|| format!("failed to read {}", libc_path.display()))?;
+    let map = libc::generate_map(&data)?;
+
+    let target_file = File::create(&out)?;
+    serde_json::to_writer_pretty(target_file, &map)?;
+    println!("{} functions are mapped", map.len());
+
+    Ok(())
+}
+
 pub fn do_diff(path1: PathBuf, path2: PathBuf) -> Result<()> {
     let profile1 = profile_util::read_seccomp_profile(&path1)?;
     let profile2 = profile_util::read_seccomp_profile(&path2)?;
@@ -121,7 +164,9 @@ pub fn handle_static_analyzer(static_: StaticSubCommand) -> Result<()> {
             out,
             lang,
             map,
-        } => do_run(bin, out, map, &lang)?,
+            rootfs,
+        } => do_run(bin, out, map, &lang, rootfs)?,
+        StaticSubCommand::GenMap { libc, out } => do_gen_map(libc, out)?,
         StaticSubCommand::Diff { path1, path2 } => do_diff(path1, path2)?,
         StaticSubCommand::Merge { paths, out } => do_merge(paths, out)?,
     };